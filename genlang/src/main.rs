@@ -1,5 +1,10 @@
+#![allow(clippy::upper_case_acronyms)]
+
 mod params;
 mod tinygp;
+use params::{
+    Evolution, Initialization, Metric, Params, ProblemFormat, SelectionScheme, PARAMS_EXTENSION,
+};
 use std::fs::{self, metadata, File};
 use std::io::{self, Write};
 use structopt::StructOpt;
@@ -16,15 +21,45 @@ struct Args {
     #[structopt(short, long)]
     output: Option<String>,
 
+    /// Initialization of the first population: full, grow or ramped
+    #[structopt(long)]
+    init: Option<Initialization>,
+
+    /// Fitness metric: abs, mse, rmse, max, hits or relative
+    #[structopt(long)]
+    metric: Option<Metric>,
+
+    /// Evolution mode: steady or generational
+    #[structopt(long)]
+    evolution: Option<Evolution>,
+
+    /// Parent selection: tournament, roulette, rank, lexicase or epsilon-lexicase
+    #[structopt(long)]
+    selection: Option<SelectionScheme>,
+
+    /// Problem file format: dat, classic, csv or json, detected by default
+    #[structopt(long)]
+    format: Option<ProblemFormat>,
+
+    /// Runs a program written in the text syntax on the problem instead of evolving one
+    #[structopt(long)]
+    solution: Option<String>,
+
+    /// The solution is written in the dialect of the old string interpreter
+    #[structopt(long, requires = "solution")]
+    old_syntax: bool,
+
+    /// Overrides a parameter of the problem, e.g. -p POPSIZE=100
+    #[structopt(short, long = "param", number_of_values = 1)]
+    params: Vec<String>,
+
     problempath: String,
 }
 
 fn main() {
     // env_logger::init();
     env_logger::Builder::from_default_env()
-        .format(|buf, record| {
-            writeln!(buf, "{}: {}", record.level(), record.args())
-        })
+        .format(|buf, record| writeln!(buf, "{}: {}", record.level(), record.args()))
         .init();
 
     // logging: set environment variable RUST_LOG to one of the levels
@@ -36,6 +71,27 @@ fn main() {
 
     let args = Args::from_args();
 
+    let init = args.init;
+    let metric = args.metric;
+    let evolution = args.evolution;
+    let selection = args.selection;
+    let overrides = args.params.clone();
+    let configure = |params: &mut Params| {
+        if let Some(init) = init {
+            params.initialization = init;
+        }
+        if let Some(metric) = metric {
+            params.metric = metric;
+        }
+        if let Some(evolution) = evolution {
+            params.evolution = evolution;
+        }
+        if let Some(selection) = selection {
+            params.selection = selection;
+        }
+        overrides.iter().try_for_each(|pair| params.set_pair(pair))
+    };
+
     let md = metadata(&args.problempath).expect("Incorrect PROBLEMPATH");
     if md.is_file() {
         let writer: Box<dyn Write> = match args.output {
@@ -43,31 +99,80 @@ fn main() {
             None => Box::new(io::stdout()),
         };
 
-        let mut tgp = TinyGP::from_problem(&args.problempath, args.seed, writer).unwrap();
-        tgp.evolve(args.generations);
+        if let Some(solution) = &args.solution {
+            let text = fs::read_to_string(solution).expect("Cannot read the solution");
+            if let Err(e) = tinygp::replay(
+                &args.problempath,
+                args.format,
+                &text,
+                args.old_syntax,
+                writer,
+                &configure,
+            ) {
+                eprintln!("Cannot run {solution}: {e}");
+                std::process::exit(1);
+            }
+            return;
+        }
+
+        match TinyGP::from_problem(
+            &args.problempath,
+            args.format,
+            args.seed,
+            writer,
+            &configure,
+        ) {
+            Ok(mut tgp) => tgp.evolve(args.generations),
+            Err(e) => {
+                eprintln!("Cannot load {}: {e}", args.problempath);
+                std::process::exit(1);
+            }
+        }
     } else if md.is_dir() {
+        if args.solution.is_some() {
+            panic!("A solution can only be run on a single problem file");
+        }
         let base_path = &args
             .output
             .expect("Output path must be specified for a problem suite");
-        let md = metadata(&base_path).expect("Output path does not exist");
+        let md = metadata(base_path).expect("Output path does not exist");
         if !md.is_dir() {
             panic!("Output path is not a directory")
         }
         for entry in fs::read_dir(&args.problempath).expect("Cannot read directory at PROBLEMPATH")
         {
-            let entry = entry.expect("wtf");
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    eprintln!("Skipping an entry of {}: {e}", args.problempath);
+                    continue;
+                }
+            };
             let input = entry.path();
-            let output = format!("{}{}", base_path, entry.file_name().to_str().unwrap());
+            let is_sidecar = input.extension().is_some_and(|e| e == PARAMS_EXTENSION);
+            if !input.is_file() || is_sidecar {
+                continue;
+            }
+            // problems are read and results written the same way, so a failure only skips the file
+            let Some(path) = input.to_str() else {
+                eprintln!("Skipping {}: the path is not valid UTF-8", input.display());
+                continue;
+            };
+            let output = format!("{}{}", base_path, entry.file_name().to_string_lossy());
             println!("{output}");
-            if entry.path().is_file() {
-                let writer: Box<dyn Write> =
-                    Box::new(File::create(output).expect("Could not create file"));
-                let mut tgp =
-                    TinyGP::from_problem(input.to_str().unwrap(), args.seed, writer).unwrap();
-                tgp.evolve(args.generations);
+            let writer: Box<dyn Write> = match File::create(&output) {
+                Ok(file) => Box::new(file),
+                Err(e) => {
+                    eprintln!("Skipping {}: cannot create {output}: {e}", input.display());
+                    continue;
+                }
+            };
+            match TinyGP::from_problem(path, args.format, args.seed, writer, &configure) {
+                Ok(mut tgp) => tgp.evolve(args.generations),
+                Err(e) => eprintln!("Skipping {}: {e}", input.display()),
             }
         }
     } else {
         panic!("PROBLEMPATH is not a dir, not a file, what is it?");
     }
-}
\ No newline at end of file
+}
@@ -1,92 +1,922 @@
-use std::{error::Error, fmt::Display};
+use serde_derive::Deserialize;
+use std::{error::Error, fmt::Display, path::Path, str::FromStr};
 
 pub type Case = (Vec<f32>, Vec<f32>);
 
+// parameters of `problem.csv` can be given in `problem.params`
+pub const PARAMS_EXTENSION: &str = "params";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Initialization {
+    Full,
+    Grow,
+    RampedHalfAndHalf,
+}
+
+impl FromStr for Initialization {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('_', "").as_str() {
+            "full" => Ok(Initialization::Full),
+            "grow" => Ok(Initialization::Grow),
+            "ramped" | "rampedhalfandhalf" => Ok(Initialization::RampedHalfAndHalf),
+            _ => Err(format!(
+                "Unknown initialization '{s}', expected full, grow or ramped"
+            )),
+        }
+    }
+}
+
+// how the outputs of a program are scored against the targets, see tinygp/fitness.rs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Metric {
+    SumAbs,
+    MeanSquared,
+    Rmse,
+    MaxError,
+    // number of outputs further than HIT_TOLERANCE from the target
+    HitCount,
+    RelativeError,
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('_', "").as_str() {
+            "sumabs" | "abs" => Ok(Metric::SumAbs),
+            "meansquared" | "mse" => Ok(Metric::MeanSquared),
+            "rmse" => Ok(Metric::Rmse),
+            "maxerror" | "max" => Ok(Metric::MaxError),
+            "hitcount" | "hits" => Ok(Metric::HitCount),
+            "relativeerror" | "relative" => Ok(Metric::RelativeError),
+            _ => Err(format!(
+                "Unknown metric '{s}', expected abs, mse, rmse, max, hits or relative"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Evolution {
+    // every child immediately replaces the loser of a negative tournament
+    SteadyState,
+    // a whole new population is bred, only the ELITISM best individuals survive
+    Generational,
+}
+
+impl FromStr for Evolution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace(['_', '-'], "").as_str() {
+            "steady" | "steadystate" => Ok(Evolution::SteadyState),
+            "generational" => Ok(Evolution::Generational),
+            _ => Err(format!(
+                "Unknown evolution '{s}', expected steady or generational"
+            )),
+        }
+    }
+}
+
+// what reading or writing a register past MEMSIZE does
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutOfRange {
+    // the run stops with a semantic error
+    Error,
+    // the register number is taken modulo MEMSIZE
+    Wrap,
+    // reads give 0 and writes are dropped
+    Zero,
+}
+
+impl FromStr for OutOfRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "error" => Ok(OutOfRange::Error),
+            "wrap" => Ok(OutOfRange::Wrap),
+            "zero" => Ok(OutOfRange::Zero),
+            _ => Err(format!(
+                "Unknown out of range behaviour '{s}', expected error, wrap or zero"
+            )),
+        }
+    }
+}
+
+// what the registers hold when a program starts on a case
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MemoryInit {
+    Zero,
+    // the inputs of the case, registers past them are 0
+    Inputs,
+    // CONSTANTS, the same for every case
+    Random,
+}
+
+impl FromStr for MemoryInit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "zero" => Ok(MemoryInit::Zero),
+            "inputs" => Ok(MemoryInit::Inputs),
+            "random" => Ok(MemoryInit::Random),
+            _ => Err(format!(
+                "Unknown memory initialization '{s}', expected zero, inputs or random"
+            )),
+        }
+    }
+}
+
+// how parents are picked, see tinygp/selection.rs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SelectionScheme {
+    Tournament,
+    Roulette,
+    Rank,
+    Lexicase,
+    EpsilonLexicase,
+}
+
+impl FromStr for SelectionScheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace(['_', '-'], "").as_str() {
+            "tournament" => Ok(SelectionScheme::Tournament),
+            "roulette" => Ok(SelectionScheme::Roulette),
+            "rank" => Ok(SelectionScheme::Rank),
+            "lexicase" => Ok(SelectionScheme::Lexicase),
+            "epsilonlexicase" => Ok(SelectionScheme::EpsilonLexicase),
+            _ => Err(format!(
+                "Unknown selection '{s}', expected tournament, roulette, rank, lexicase or epsilon-lexicase"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProblemFormat {
+    // memsize | ncases header, inputs and outputs split by the separator
+    Dat,
+    // header and cases of the original TinyGP
+    Classic,
+    // header row with column names, columns picked by INPUTS and OUTPUTS
+    Csv,
+    // [{"in": [..], "out": [..]}, ..]
+    Json,
+}
+
+impl ProblemFormat {
+    // only CSV and JSON are told apart by extension, others are detected from the header
+    pub fn from_path(path: &str) -> Option<ProblemFormat> {
+        let extension = Path::new(path).extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "csv" => Some(ProblemFormat::Csv),
+            "json" => Some(ProblemFormat::Json),
+            _ => None,
+        }
+    }
+
+    // five numeric header fields are only found in the original TinyGP format
+    fn detect(header: &[(usize, &str)]) -> ProblemFormat {
+        if header.len() == 5 && header.iter().all(|(_, t)| t.parse::<f32>().is_ok()) {
+            ProblemFormat::Classic
+        } else {
+            ProblemFormat::Dat
+        }
+    }
+}
+
+impl FromStr for ProblemFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "dat" => Ok(ProblemFormat::Dat),
+            "classic" => Ok(ProblemFormat::Classic),
+            "csv" => Ok(ProblemFormat::Csv),
+            "json" => Ok(ProblemFormat::Json),
+            _ => Err(format!(
+                "Unknown format '{s}', expected dat, classic, csv or json"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Params {
-    pub seed: u64,
+    pub seed: Option<u64>,
     pub memsize: usize,
+    pub out_of_range: OutOfRange,
+    pub memory_init: MemoryInit,
+    // registers of MEMORY_INIT=random, the missing ones are drawn from MIN_RANDOM..MAX_RANDOM
+    // when the run starts
+    pub constants: Vec<f32>,
     pub popsize: usize,
     pub depth: usize,
     pub crossover_prob: f32,
+    pub subtree_mut_prob: f32,
     pub pmut_per_node: f32,
+    pub pperturb_per_num: f32,
+    pub perturb_range: f32,
+    pub min_random: f32,
+    pub max_random: f32,
+    pub selection: SelectionScheme,
     pub tournament_size: usize,
     pub acceptable_error: f32,
+    // error added for every target the program did not output, and for every output past the targets
+    pub missing_output_penalty: f32,
+    pub extra_output_penalty: f32,
+    // error added to a case on which the program crashed or ran for too long
+    pub semantic_error_penalty: f32,
+    pub max_iteration_penalty: f32,
+    // iterations of a single WHILE, statements and expressions of a whole run and outputs
+    // a program may make on one case
+    pub max_iterations: usize,
+    pub max_steps: usize,
+    pub max_output_len: usize,
+    pub metric: Metric,
+    pub hit_tolerance: f32,
+    // evaluation threads, 0 uses every core
+    pub threads: usize,
+    // children bred from the same population before they are evaluated and inserted
+    pub eval_batch: usize,
+    // evaluated programs remembered by the fitness cache, 0 disables it
+    pub cache_size: usize,
+    pub evolution: Evolution,
+    pub elitism: usize,
+    // fitness lost per token of program length
+    pub parsimony: f32,
+    // chance that a child longer than the average is rejected without evaluation
+    pub tarpeian_prob: f32,
+    // longer offspring are replaced by a copy of their parent
+    pub max_program_len: usize,
+    // chance that the shorter of two selected parents wins
+    pub size_tournament_prob: f32,
+    pub initialization: Initialization,
+    // CSV columns read as inputs and outputs
+    pub input_columns: Vec<String>,
+    pub output_columns: Vec<String>,
 }
 
 impl Params {
-    pub fn from_string(data: String) -> Result<(Params, Vec<Case>), Box<dyn Error>> {
-        let lines: Vec<&str> = data.split('\n').collect();
-        let header: Vec<&str> = lines[0].trim().split([' ', '\t']).collect();
-        let memsize: usize = header[0].parse()?;
-        let separator: &str = header[1];
-        let num_cases: usize = header[2].parse()?;
-
-        let mut cases: Vec<Case> = Vec::with_capacity(num_cases);
-        for i in 0..num_cases {
-            let tokens: Vec<&str> = lines[i + 1]
-                .trim()
-                .split([' ', '\t'])
-                .filter(|t| !t.is_empty())
-                .collect();
-            let split_pos = tokens.iter().position(|&t| t == separator).expect("No pipe '|' found in the input");
-            let (inputs, pipe_and_outputs) = tokens.split_at(split_pos);
-            let outputs = &pipe_and_outputs[1..];
-            let inputs = inputs
-                .iter()
-                .map(|t| t.parse().unwrap())
-                .collect::<Vec<f32>>();
-
-            let outputs = outputs
-                .iter()
-                .map(|t| t.parse().unwrap())
-                .collect::<Vec<f32>>();
-
-            cases.push((Vec::from(inputs), Vec::from(outputs)));
-        }
-
-        Ok((
-            Params {
-                seed: 5,
-                memsize,
-                ..Default::default()
-            },
-            cases,
-        ))
+    // reads the cases, parameters given in the problem override the current ones
+    pub fn read_problem(
+        &mut self,
+        data: &str,
+        format: Option<ProblemFormat>,
+    ) -> Result<Vec<Case>, ProblemParseError> {
+        let cases = match format {
+            Some(ProblemFormat::Csv) => read_csv(data, self)?,
+            Some(ProblemFormat::Json) => read_json(data)?,
+            _ => {
+                let lines: Vec<&str> = data.split('\n').collect();
+
+                // optional KEY=VALUE lines before the header
+                let mut line = 0;
+                while line < lines.len() && lines[line].contains('=') {
+                    self.set_line(line + 1, lines[line])?;
+                    line += 1;
+                }
+
+                let header = tokenize(lines.get(line).ok_or(ProblemParseError::MissingHeader)?);
+                match format.unwrap_or_else(|| ProblemFormat::detect(&header)) {
+                    ProblemFormat::Classic => read_classic(&lines, line, &header, self)?,
+                    _ => read_dat(&lines, line, &header, self)?,
+                }
+            }
+        };
+        if self.memsize == 0 {
+            self.memsize = cases.first().map_or(0, |(inputs, _)| inputs.len()).max(1);
+        }
+        Ok(cases)
+    }
+
+    // reads a sidecar file made only of KEY=VALUE lines
+    pub fn read_parameters(&mut self, data: &str) -> Result<(), ProblemParseError> {
+        for (i, line) in data.lines().enumerate() {
+            self.set_line(i + 1, line)?;
+        }
+        Ok(())
+    }
+
+    fn set_line(&mut self, line: usize, text: &str) -> Result<(), ProblemParseError> {
+        for (column, pair) in tokenize(text) {
+            self.set_pair(pair)
+                .map_err(|reason| ProblemParseError::BadParameter {
+                    line,
+                    column,
+                    reason,
+                })?;
+        }
+        Ok(())
+    }
+
+    pub fn set_pair(&mut self, pair: &str) -> Result<(), String> {
+        let (key, value) = pair
+            .split_once('=')
+            .ok_or_else(|| format!("Expected KEY=VALUE, got '{pair}'"))?;
+        self.set(key, value)
+    }
+
+    // keys are the same as in the Display output
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        fn parse<T: FromStr>(key: &str, value: &str) -> Result<T, String>
+        where
+            T::Err: Display,
+        {
+            value
+                .parse()
+                .map_err(|e| format!("Invalid value '{value}' for {key}: {e}"))
+        }
+
+        // an empty list names no columns
+        fn columns(value: &str) -> Vec<String> {
+            value
+                .split(',')
+                .filter(|v| !v.is_empty())
+                .map(str::to_owned)
+                .collect()
+        }
+
+        match key.to_uppercase().as_str() {
+            "SEED" => self.seed = Some(parse(key, value)?),
+            "MEMSIZE" => self.memsize = parse(key, value)?,
+            "OUT_OF_RANGE" => self.out_of_range = parse(key, value)?,
+            "MEMORY_INIT" => self.memory_init = parse(key, value)?,
+            "CONSTANTS" => {
+                self.constants = value
+                    .split(',')
+                    .filter(|v| !v.trim().is_empty())
+                    .map(|v| parse(key, v.trim()))
+                    .collect::<Result<_, _>>()?
+            }
+            "POPSIZE" => self.popsize = parse(key, value)?,
+            "DEPTH" => self.depth = parse(key, value)?,
+            "CROSSOVER_PROB" => self.crossover_prob = parse(key, value)?,
+            "SUBTREE_MUT_PROB" => self.subtree_mut_prob = parse(key, value)?,
+            "PMUT_PER_NODE" => self.pmut_per_node = parse(key, value)?,
+            "PPERTURB_PER_NUM" => self.pperturb_per_num = parse(key, value)?,
+            "PERTURB_RANGE" => self.perturb_range = parse(key, value)?,
+            "MIN_RANDOM" => self.min_random = parse(key, value)?,
+            "MAX_RANDOM" => self.max_random = parse(key, value)?,
+            "SELECTION" => self.selection = parse(key, value)?,
+            "TSIZE" => self.tournament_size = parse(key, value)?,
+            "ACCEPTABLE_ERROR" => self.acceptable_error = parse(key, value)?,
+            "MISSING_OUTPUT_PENALTY" => self.missing_output_penalty = parse(key, value)?,
+            "EXTRA_OUTPUT_PENALTY" => self.extra_output_penalty = parse(key, value)?,
+            "SEMANTIC_ERROR_PENALTY" => self.semantic_error_penalty = parse(key, value)?,
+            "MAX_ITERATION_PENALTY" => self.max_iteration_penalty = parse(key, value)?,
+            "MAX_ITERATIONS" => self.max_iterations = parse(key, value)?,
+            "MAX_STEPS" => self.max_steps = parse(key, value)?,
+            "MAX_OUTPUT_LEN" => self.max_output_len = parse(key, value)?,
+            "METRIC" => self.metric = parse(key, value)?,
+            "HIT_TOLERANCE" => self.hit_tolerance = parse(key, value)?,
+            "THREADS" => self.threads = parse(key, value)?,
+            "EVAL_BATCH" => self.eval_batch = parse(key, value)?,
+            "CACHE_SIZE" => self.cache_size = parse(key, value)?,
+            "EVOLUTION" => self.evolution = parse(key, value)?,
+            "ELITISM" => self.elitism = parse(key, value)?,
+            "PARSIMONY" => self.parsimony = parse(key, value)?,
+            "TARPEIAN_PROB" => self.tarpeian_prob = parse(key, value)?,
+            "MAX_PROGRAM_LEN" => self.max_program_len = parse(key, value)?,
+            "SIZE_TOURNAMENT_PROB" => self.size_tournament_prob = parse(key, value)?,
+            "INITIALIZATION" => self.initialization = parse(key, value)?,
+            "INPUTS" => self.input_columns = columns(value),
+            "OUTPUTS" => self.output_columns = columns(value),
+            _ => return Err(format!("Unknown parameter '{key}'")),
+        }
+        Ok(())
     }
 }
 
+// memsize separator ncases
+// followed by ncases lines of: inputs separator outputs
+fn read_dat(
+    lines: &[&str],
+    header_index: usize,
+    header: &[(usize, &str)],
+    params: &mut Params,
+) -> Result<Vec<Case>, ProblemParseError> {
+    let header_line = header_index + 1;
+    check_header_len(header_line, header, "memsize separator ncases")?;
+    let memsize = parse_count(header_line, header[0])?;
+    let separator: &str = header[1].1;
+    let num_cases = parse_count(header_line, header[2])?;
+
+    let mut cases: Vec<Case> = Vec::with_capacity(num_cases);
+    for (i, case_line) in lines.iter().enumerate().skip(header_line) {
+        let line = i + 1;
+        let tokens = tokenize(case_line);
+        if tokens.is_empty() {
+            continue;
+        }
+        let split_pos = tokens
+            .iter()
+            .position(|&(_, t)| t == separator)
+            .ok_or_else(|| ProblemParseError::MissingSeparator {
+                line,
+                separator: separator.to_owned(),
+            })?;
+        let (inputs, separator_and_outputs) = tokens.split_at(split_pos);
+        let inputs = parse_floats(line, inputs)?;
+        let outputs = parse_floats(line, &separator_and_outputs[1..])?;
+
+        if let Some((first_inputs, first_outputs)) = cases.first() {
+            check_arity(
+                Some(line),
+                cases.len() + 1,
+                (first_inputs.len(), first_outputs.len()),
+                &inputs,
+                &outputs,
+            )?;
+        }
+        cases.push((inputs, outputs));
+    }
+    check_case_count(header_line, num_cases, &cases)?;
+
+    params.memsize = memsize;
+    Ok(cases)
+}
+
+// the original TinyGP header: varnumber randomnumber minrandom maxrandom fitnesscases
+// followed by fitnesscases lines of: varnumber inputs and the target
+fn read_classic(
+    lines: &[&str],
+    header_index: usize,
+    header: &[(usize, &str)],
+    params: &mut Params,
+) -> Result<Vec<Case>, ProblemParseError> {
+    let header_line = header_index + 1;
+    check_header_len(
+        header_line,
+        header,
+        "varnumber randomnumber minrandom maxrandom fitnesscases",
+    )?;
+    let varnumber = parse_count(header_line, header[0])?;
+    // constants are grown inline, so the size of the constant table does not matter
+    parse_count(header_line, header[1])?;
+    let [min_random, max_random] = [header[2], header[3]].map(|(column, token)| {
+        token
+            .parse::<f32>()
+            .map_err(|_| ProblemParseError::BadHeader {
+                line: header_line,
+                column,
+                reason: format!("'{token}' is not a number"),
+            })
+    });
+    let num_cases = parse_count(header_line, header[4])?;
+
+    let mut cases: Vec<Case> = Vec::with_capacity(num_cases);
+    for (i, case_line) in lines.iter().enumerate().skip(header_line) {
+        let line = i + 1;
+        let tokens = tokenize(case_line);
+        if tokens.is_empty() {
+            continue;
+        }
+        let mut inputs = parse_floats(line, &tokens)?;
+        let target = inputs.pop().unwrap();
+        let outputs = vec![target];
+        check_arity(
+            Some(line),
+            cases.len() + 1,
+            (varnumber, 1),
+            &inputs,
+            &outputs,
+        )?;
+        cases.push((inputs, outputs));
+    }
+    check_case_count(header_line, num_cases, &cases)?;
+
+    params.memsize = varnumber.max(1);
+    params.min_random = min_random?;
+    params.max_random = max_random?;
+    Ok(cases)
+}
+
+// a header row naming the columns, then one case per row
+fn read_csv(data: &str, params: &Params) -> Result<Vec<Case>, ProblemParseError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(data.as_bytes());
+    let headers = reader.headers().map_err(csv_error)?.clone();
+    let find = |names: &[String]| {
+        names
+            .iter()
+            .map(|name| {
+                headers
+                    .iter()
+                    .position(|h| h == name)
+                    .ok_or_else(|| ProblemParseError::UnknownColumn { name: name.clone() })
+            })
+            .collect::<Result<Vec<usize>, ProblemParseError>>()
+    };
+    let rest = |picked: &[usize]| -> Vec<usize> {
+        (0..headers.len()).filter(|i| !picked.contains(i)).collect()
+    };
+    // without names the last column is the output and the others are inputs
+    let (input_columns, output_columns) = match (
+        params.input_columns.is_empty(),
+        params.output_columns.is_empty(),
+    ) {
+        (true, true) => {
+            let last = headers.len().saturating_sub(1);
+            ((0..last).collect(), vec![last])
+        }
+        (false, true) => {
+            let inputs = find(&params.input_columns)?;
+            let outputs = rest(&inputs);
+            (inputs, outputs)
+        }
+        (true, false) => {
+            let outputs = find(&params.output_columns)?;
+            (rest(&outputs), outputs)
+        }
+        (false, false) => (find(&params.input_columns)?, find(&params.output_columns)?),
+    };
+
+    let mut cases = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(csv_error)?;
+        let line = record.position().map_or(0, |p| p.line() as usize);
+        let tokens = |columns: &[usize]| -> Vec<(usize, &str)> {
+            columns.iter().map(|&c| (c + 1, &record[c])).collect()
+        };
+        let inputs = parse_floats(line, &tokens(&input_columns))?;
+        let outputs = parse_floats(line, &tokens(&output_columns))?;
+        cases.push((inputs, outputs));
+    }
+    Ok(cases)
+}
+
+// the reader only knows the record an error is in, not the column
+fn csv_error(e: csv::Error) -> ProblemParseError {
+    ProblemParseError::Malformed {
+        line: e.position().map_or(0, |p| p.line() as usize),
+        column: None,
+        reason: e.to_string(),
+    }
+}
+
+#[derive(Deserialize)]
+struct JsonCase {
+    #[serde(rename = "in")]
+    inputs: Vec<f32>,
+    #[serde(rename = "out")]
+    outputs: Vec<f32>,
+}
+
+// the parsed cases no longer know their lines, so arity errors only name the case
+fn read_json(data: &str) -> Result<Vec<Case>, ProblemParseError> {
+    let json_cases: Vec<JsonCase> =
+        serde_json::from_str(data).map_err(|e| ProblemParseError::Malformed {
+            line: e.line(),
+            column: Some(e.column()),
+            reason: e.to_string(),
+        })?;
+    let mut cases: Vec<Case> = Vec::with_capacity(json_cases.len());
+    for (i, JsonCase { inputs, outputs }) in json_cases.into_iter().enumerate() {
+        if let Some((first_inputs, first_outputs)) = cases.first() {
+            check_arity(
+                None,
+                i + 1,
+                (first_inputs.len(), first_outputs.len()),
+                &inputs,
+                &outputs,
+            )?;
+        }
+        cases.push((inputs, outputs));
+    }
+    Ok(cases)
+}
+
+fn check_header_len(
+    line: usize,
+    header: &[(usize, &str)],
+    layout: &str,
+) -> Result<(), ProblemParseError> {
+    if header.len() != layout.split(' ').count() {
+        return Err(ProblemParseError::BadHeader {
+            line,
+            column: 1,
+            reason: format!("expected '{layout}', got {} fields", header.len()),
+        });
+    }
+    Ok(())
+}
+
+fn parse_count(line: usize, (column, token): (usize, &str)) -> Result<usize, ProblemParseError> {
+    token.parse().map_err(|e| ProblemParseError::BadHeader {
+        line,
+        column,
+        reason: format!("'{token}' is not a count: {e}"),
+    })
+}
+
+fn check_arity(
+    line: Option<usize>,
+    case: usize,
+    expected: (usize, usize),
+    inputs: &[f32],
+    outputs: &[f32],
+) -> Result<(), ProblemParseError> {
+    let found = (inputs.len(), outputs.len());
+    if found != expected {
+        return Err(ProblemParseError::InconsistentArity {
+            line,
+            case,
+            expected,
+            found,
+        });
+    }
+    Ok(())
+}
+
+fn check_case_count(line: usize, expected: usize, cases: &[Case]) -> Result<(), ProblemParseError> {
+    if cases.len() != expected {
+        return Err(ProblemParseError::WrongCaseCount {
+            line,
+            expected,
+            found: cases.len(),
+        });
+    }
+    Ok(())
+}
+
+// whitespace separated tokens with their 1-based columns
+fn tokenize(line: &str) -> Vec<(usize, &str)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, c) in line.char_indices().chain([(line.len(), ' ')]) {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                tokens.push((s + 1, &line[s..i]));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => (),
+        }
+    }
+    tokens
+}
+
+fn parse_floats(line: usize, tokens: &[(usize, &str)]) -> Result<Vec<f32>, ProblemParseError> {
+    tokens
+        .iter()
+        .map(|&(column, token)| {
+            token.parse().map_err(|_| ProblemParseError::BadFloat {
+                line,
+                column,
+                token: token.to_owned(),
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProblemParseError {
+    MissingHeader,
+    BadHeader {
+        line: usize,
+        column: usize,
+        reason: String,
+    },
+    BadParameter {
+        line: usize,
+        column: usize,
+        reason: String,
+    },
+    MissingSeparator {
+        line: usize,
+        separator: String,
+    },
+    BadFloat {
+        line: usize,
+        column: usize,
+        token: String,
+    },
+    WrongCaseCount {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    UnknownColumn {
+        name: String,
+    },
+    // CSV or JSON the respective parser could not read, CSV errors have no column
+    Malformed {
+        line: usize,
+        column: Option<usize>,
+        reason: String,
+    },
+    // (inputs, outputs) of the first case and of the offending one, the 1-based number
+    // of the case and its line, which JSON cases don't have
+    InconsistentArity {
+        line: Option<usize>,
+        case: usize,
+        expected: (usize, usize),
+        found: (usize, usize),
+    },
+}
+
+impl Display for ProblemParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProblemParseError::MissingHeader => write!(f, "missing problem header"),
+            ProblemParseError::BadHeader {
+                line,
+                column,
+                reason,
+            } => write!(f, "{line}:{column}: invalid header: {reason}"),
+            ProblemParseError::BadParameter {
+                line,
+                column,
+                reason,
+            } => write!(f, "{line}:{column}: {reason}"),
+            ProblemParseError::MissingSeparator { line, separator } => {
+                write!(f, "{line}: no separator '{separator}' found")
+            }
+            ProblemParseError::BadFloat {
+                line,
+                column,
+                token,
+            } => write!(f, "{line}:{column}: '{token}' is not a number"),
+            ProblemParseError::WrongCaseCount {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{line}: header declares {expected} cases, but {found} were found"
+            ),
+            ProblemParseError::UnknownColumn { name } => write!(f, "no column named '{name}'"),
+            ProblemParseError::Malformed {
+                line,
+                column: Some(column),
+                reason,
+            } => write!(f, "{line}:{column}: {reason}"),
+            ProblemParseError::Malformed {
+                line,
+                column: None,
+                reason,
+            } => write!(f, "{line}: {reason}"),
+            ProblemParseError::InconsistentArity {
+                line,
+                case,
+                expected,
+                found,
+            } => {
+                if let Some(line) = line {
+                    write!(f, "{line}: ")?;
+                }
+                write!(
+                    f,
+                    "case {case} has {} inputs and {} outputs, expected {} and {}",
+                    found.0, found.1, expected.0, expected.1
+                )
+            }
+        }
+    }
+}
+
+impl Error for ProblemParseError {}
+
 impl Default for Params {
     fn default() -> Self {
         Self {
-            seed: Default::default(),
+            seed: None,
             memsize: 0,
+            out_of_range: OutOfRange::Error,
+            memory_init: MemoryInit::Zero,
+            constants: Vec::new(),
             popsize: 10,
             depth: 5,
             crossover_prob: 0.9,
+            subtree_mut_prob: 0.5,
             pmut_per_node: 0.05,
+            pperturb_per_num: 0.1,
+            perturb_range: 1.0,
+            min_random: -10.0,
+            max_random: 10.0,
+            selection: SelectionScheme::Tournament,
             tournament_size: 2,
             acceptable_error: -1e-3,
+            missing_output_penalty: 1000.0,
+            extra_output_penalty: 1.0,
+            semantic_error_penalty: f32::INFINITY,
+            max_iteration_penalty: 0.0,
+            max_iterations: 100,
+            max_steps: 100000,
+            max_output_len: 1000,
+            metric: Metric::SumAbs,
+            hit_tolerance: 0.01,
+            threads: 0,
+            eval_batch: 1,
+            cache_size: 10000,
+            evolution: Evolution::SteadyState,
+            elitism: 1,
+            parsimony: 0.0,
+            tarpeian_prob: 0.0,
+            max_program_len: 10000,
+            size_tournament_prob: 0.0,
+            initialization: Initialization::RampedHalfAndHalf,
+            input_columns: Vec::new(),
+            output_columns: Vec::new(),
         }
     }
 }
 
 impl Display for Params {
+    // every parameter in a form set_pair reads back, without a seed the line is left out
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(seed) = self.seed {
+            writeln!(f, "SEED={seed}")?;
+        }
         f.write_str(
             format!(
-                "SEED={}
+                "MEMSIZE={}
+OUT_OF_RANGE={:?}
+MEMORY_INIT={:?}
+CONSTANTS={}
 POPSIZE={}
 DEPTH={}
 CROSSOVER_PROB={}
+SUBTREE_MUT_PROB={}
 PMUT_PER_NODE={}
+PPERTURB_PER_NUM={}
+PERTURB_RANGE={}
+MIN_RANDOM={}
+MAX_RANDOM={}
+SELECTION={:?}
 TSIZE={}
+ACCEPTABLE_ERROR={}
+MISSING_OUTPUT_PENALTY={}
+EXTRA_OUTPUT_PENALTY={}
+SEMANTIC_ERROR_PENALTY={}
+MAX_ITERATION_PENALTY={}
+MAX_ITERATIONS={}
+MAX_STEPS={}
+MAX_OUTPUT_LEN={}
+METRIC={:?}
+HIT_TOLERANCE={}
+THREADS={}
+EVAL_BATCH={}
+CACHE_SIZE={}
+EVOLUTION={:?}
+ELITISM={}
+PARSIMONY={}
+TARPEIAN_PROB={}
+MAX_PROGRAM_LEN={}
+SIZE_TOURNAMENT_PROB={}
+INITIALIZATION={:?}
+INPUTS={}
+OUTPUTS={}
 ----------------------------------\n",
-                self.seed,
+                self.memsize,
+                self.out_of_range,
+                self.memory_init,
+                self.constants
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(","),
                 self.popsize,
                 self.depth,
                 self.crossover_prob,
+                self.subtree_mut_prob,
                 self.pmut_per_node,
-                self.tournament_size
+                self.pperturb_per_num,
+                self.perturb_range,
+                self.min_random,
+                self.max_random,
+                self.selection,
+                self.tournament_size,
+                self.acceptable_error,
+                self.missing_output_penalty,
+                self.extra_output_penalty,
+                self.semantic_error_penalty,
+                self.max_iteration_penalty,
+                self.max_iterations,
+                self.max_steps,
+                self.max_output_len,
+                self.metric,
+                self.hit_tolerance,
+                self.threads,
+                self.eval_batch,
+                self.cache_size,
+                self.evolution,
+                self.elitism,
+                self.parsimony,
+                self.tarpeian_prob,
+                self.max_program_len,
+                self.size_tournament_prob,
+                self.initialization,
+                self.input_columns.join(","),
+                self.output_columns.join(",")
             )
             .as_str(),
         )
@@ -95,17 +925,31 @@ TSIZE={}
 
 #[cfg(test)]
 mod tests {
-    use crate::params::Params;
+    use crate::params::{
+        Case, Evolution, Initialization, MemoryInit, Metric, OutOfRange, Params, ProblemFormat,
+        ProblemParseError, SelectionScheme,
+    };
+
+    // reads the problem into default parameters, in the given format or detected from the header
+    fn from_string(
+        data: String,
+        format: Option<ProblemFormat>,
+    ) -> Result<(Params, Vec<Case>), ProblemParseError> {
+        let mut params = Params::default();
+        let cases = params.read_problem(&data, format)?;
+        Ok((params, cases))
+    }
 
     #[test]
     fn test_read_params() {
-        let (_param, cases) = match Params::from_string(
+        let (_param, cases) = match from_string(
             "2 | 3
 2 2 | 4
 1 3 | 4
 10 20 | 30
 "
-                .to_owned(),
+            .to_owned(),
+            None,
         ) {
             Ok(p) => p,
             Err(_) => panic!("Read problem failed"),
@@ -117,4 +961,310 @@ mod tests {
             assert_eq!(targets.len(), 1);
         });
     }
+
+    #[test]
+    fn test_read_parameter_block() {
+        let (params, cases) = from_string(
+            "POPSIZE=100 DEPTH=4
+TSIZE=3 SEED=7 crossover_prob=0.5 INITIALIZATION=grow
+2 | 1
+2 2 | 4
+"
+            .to_owned(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(cases.len(), 1);
+        assert_eq!(params.memsize, 2);
+        assert_eq!(params.popsize, 100);
+        assert_eq!(params.depth, 4);
+        assert_eq!(params.tournament_size, 3);
+        assert_eq!(params.seed, Some(7));
+        assert_eq!(params.crossover_prob, 0.5);
+        assert_eq!(params.initialization, Initialization::Grow);
+    }
+
+    #[test]
+    fn test_read_parameter_block_errors() {
+        let unknown = from_string("POPSIZE=10 WIDTH=3\n1 | 1\n1 | 1\n".to_owned(), None);
+        assert!(matches!(
+            unknown,
+            Err(ProblemParseError::BadParameter { line: 1, column: 12, ref reason })
+                if reason.contains("WIDTH")
+        ));
+        let invalid = from_string("POPSIZE=many\n1 | 1\n1 | 1\n".to_owned(), None);
+        assert!(invalid.unwrap_err().to_string().contains("POPSIZE"));
+    }
+
+    #[test]
+    fn test_read_problem_errors() {
+        let parse = |data: &str| from_string(data.to_owned(), None).err();
+
+        assert_eq!(
+            parse(""),
+            Some(ProblemParseError::BadHeader {
+                line: 1,
+                column: 1,
+                reason: "expected 'memsize separator ncases', got 0 fields".to_owned()
+            })
+        );
+        assert!(matches!(
+            parse("2 | x\n"),
+            Some(ProblemParseError::BadHeader {
+                line: 1,
+                column: 5,
+                ..
+            })
+        ));
+        assert_eq!(
+            parse("2 | 2\n1 2 | 3\n1 2 3\n"),
+            Some(ProblemParseError::MissingSeparator {
+                line: 3,
+                separator: "|".to_owned()
+            })
+        );
+        assert_eq!(
+            parse("2 | 1\n1  2.x | 3\n"),
+            Some(ProblemParseError::BadFloat {
+                line: 2,
+                column: 4,
+                token: "2.x".to_owned()
+            })
+        );
+        assert_eq!(
+            parse("2 | 3\n1 2 | 3\n\n2 2 | 4\n"),
+            Some(ProblemParseError::WrongCaseCount {
+                line: 1,
+                expected: 3,
+                found: 2
+            })
+        );
+        assert_eq!(
+            parse("2 | 2\n1 2 | 3\n2 | 4\n"),
+            Some(ProblemParseError::InconsistentArity {
+                line: Some(3),
+                case: 2,
+                expected: (2, 1),
+                found: (1, 1)
+            })
+        );
+    }
+
+    #[test]
+    fn test_read_classic() {
+        let (params, cases) = from_string(
+            "2 100 -5 5 3
+1 2 3
+3 4 7
+-1.5 0.5 -1.0
+"
+            .to_owned(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(params.memsize, 2);
+        assert_eq!(params.min_random, -5.0);
+        assert_eq!(params.max_random, 5.0);
+        assert_eq!(
+            cases,
+            vec![
+                (vec![1.0, 2.0], vec![3.0]),
+                (vec![3.0, 4.0], vec![7.0]),
+                (vec![-1.5, 0.5], vec![-1.0])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_classic_shipped_example() {
+        let data = std::fs::read_to_string("test/zad1.dat").unwrap();
+        let (params, cases) = from_string(data, None).unwrap();
+        assert_eq!(params.memsize, 1);
+        assert_eq!((params.min_random, params.max_random), (-20.0, 20.0));
+        assert_eq!(cases.len(), 100);
+        assert_eq!(cases[0], (vec![-10.0], vec![-5247.0]));
+    }
+
+    #[test]
+    fn test_read_explicit_format() {
+        let data = "1 | 1\n1 | 1\n";
+        let classic = from_string(data.to_owned(), Some(ProblemFormat::Classic));
+        assert!(matches!(
+            classic,
+            Err(ProblemParseError::BadHeader { line: 1, .. })
+        ));
+        let classic = from_string("1 0 0 1 1\n2 3\n".to_owned(), Some(ProblemFormat::Classic));
+        assert_eq!(classic.unwrap().1, vec![(vec![2.0], vec![3.0])]);
+        let arity = from_string("1 0 0 1 1\n2 3 4\n".to_owned(), None);
+        assert!(matches!(
+            arity,
+            Err(ProblemParseError::InconsistentArity {
+                line: Some(2),
+                case: 1,
+                expected: (1, 1),
+                found: (2, 1)
+            })
+        ));
+    }
+
+    #[test]
+    fn test_read_csv() {
+        let data = "x, y, noise, z
+1, 2, 0.5, 3
+3, 4, 0.1, 7
+";
+        let (params, cases) = from_string(data.to_owned(), Some(ProblemFormat::Csv)).unwrap();
+        assert_eq!(params.memsize, 3);
+        assert_eq!(cases[1], (vec![3.0, 4.0, 0.1], vec![7.0]));
+
+        let mut params = Params::default();
+        params.set_pair("INPUTS=y,x").unwrap();
+        params.set_pair("OUTPUTS=z").unwrap();
+        let cases = params.read_problem(data, Some(ProblemFormat::Csv)).unwrap();
+        assert_eq!(params.memsize, 2);
+        assert_eq!(
+            cases,
+            vec![(vec![2.0, 1.0], vec![3.0]), (vec![4.0, 3.0], vec![7.0])]
+        );
+
+        let mut params = Params::default();
+        params.set_pair("OUTPUTS=z,x").unwrap();
+        let cases = params.read_problem(data, Some(ProblemFormat::Csv)).unwrap();
+        assert_eq!(cases[0], (vec![2.0, 0.5], vec![3.0, 1.0]));
+    }
+
+    #[test]
+    fn test_read_csv_errors() {
+        let mut params = Params::default();
+        params.set_pair("INPUTS=w").unwrap();
+        assert_eq!(
+            params.read_problem("x,y\n1,2\n", Some(ProblemFormat::Csv)),
+            Err(ProblemParseError::UnknownColumn {
+                name: "w".to_owned()
+            })
+        );
+        assert_eq!(
+            from_string("x,y\n1,2\n1,b\n".to_owned(), Some(ProblemFormat::Csv)).err(),
+            Some(ProblemParseError::BadFloat {
+                line: 3,
+                column: 2,
+                token: "b".to_owned()
+            })
+        );
+        let error = from_string("x,y\n1,2\n1\n".to_owned(), Some(ProblemFormat::Csv));
+        assert!(matches!(
+            error,
+            Err(ProblemParseError::Malformed {
+                line: 3,
+                column: None,
+                ..
+            })
+        ));
+        assert!(error.unwrap_err().to_string().starts_with("3: "));
+    }
+
+    #[test]
+    fn test_read_json() {
+        let data = r#"[
+            {"in": [1, 2], "out": [3]},
+            {"in": [3, 4], "out": [7]}
+        ]"#;
+        let (params, cases) = from_string(data.to_owned(), Some(ProblemFormat::Json)).unwrap();
+        assert_eq!(params.memsize, 2);
+        assert_eq!(
+            cases,
+            vec![(vec![1.0, 2.0], vec![3.0]), (vec![3.0, 4.0], vec![7.0])]
+        );
+
+        let arity = r#"[{"in": [1], "out": [1]}, {"in": [1], "out": []}]"#;
+        assert_eq!(
+            from_string(arity.to_owned(), Some(ProblemFormat::Json)).err(),
+            Some(ProblemParseError::InconsistentArity {
+                line: None,
+                case: 2,
+                expected: (1, 1),
+                found: (1, 0)
+            })
+        );
+        assert_eq!(
+            from_string(arity.to_owned(), Some(ProblemFormat::Json))
+                .unwrap_err()
+                .to_string(),
+            "case 2 has 1 inputs and 0 outputs, expected 1 and 1"
+        );
+        // the case missing its outputs ends at line 2, column 13
+        assert!(matches!(
+            from_string("[\n  {\"in\": [1]}]".to_owned(), Some(ProblemFormat::Json)),
+            Err(ProblemParseError::Malformed {
+                line: 2,
+                column: Some(13),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_read_sidecar_parameters() {
+        let mut params = Params::default();
+        params
+            .read_parameters("POPSIZE=30\n\nINPUTS=a,b OUTPUTS=c\n")
+            .unwrap();
+        assert_eq!(params.popsize, 30);
+        assert_eq!(params.input_columns, vec!["a", "b"]);
+        assert_eq!(params.output_columns, vec!["c"]);
+        assert_eq!(
+            params.read_parameters("DEPTH=3\n POPSIZE=x"),
+            Err(ProblemParseError::BadParameter {
+                line: 2,
+                column: 2,
+                reason: "Invalid value 'x' for POPSIZE: invalid digit found in string".to_owned()
+            })
+        );
+        assert_eq!(
+            ProblemFormat::from_path("data/set.CSV"),
+            Some(ProblemFormat::Csv)
+        );
+        assert_eq!(
+            ProblemFormat::from_path("set.json"),
+            Some(ProblemFormat::Json)
+        );
+        assert_eq!(ProblemFormat::from_path("test/add.dat"), None);
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        let read_back = |params: &Params| {
+            let mut read = Params::default();
+            for line in params.to_string().lines().filter(|l| l.contains('=')) {
+                read.set_pair(line).unwrap();
+            }
+            read
+        };
+        let params = Params {
+            seed: Some(3),
+            memsize: 5,
+            popsize: 42,
+            max_steps: 77,
+            parsimony: 0.25,
+            metric: Metric::HitCount,
+            evolution: Evolution::Generational,
+            selection: SelectionScheme::EpsilonLexicase,
+            initialization: Initialization::Grow,
+            out_of_range: OutOfRange::Wrap,
+            memory_init: MemoryInit::Random,
+            constants: vec![1.5, -2.0],
+            input_columns: vec!["x".to_owned(), "y".to_owned()],
+            output_columns: vec!["z".to_owned()],
+            ..Default::default()
+        };
+        assert_eq!(read_back(&params), params);
+
+        // without a seed there is no SEED line to reject
+        let params = Params::default();
+        assert!(params.seed.is_none());
+        assert!(!params.to_string().contains("SEED"));
+        assert_eq!(read_back(&params), params);
+    }
 }
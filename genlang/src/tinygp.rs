@@ -1,24 +1,48 @@
+mod bytecode;
+mod cache;
 mod common;
+mod dialect;
 mod evolution;
 mod execution;
+mod fitness;
 mod growing;
+mod selection;
+mod syntax;
+mod validation;
 
 #[cfg(test)]
 mod interpreter_tests;
 
 use crate::params::Case;
+use crate::params::Evolution;
+use crate::params::Initialization;
+use crate::params::MemoryInit;
+use crate::params::OutOfRange;
 use crate::params::Params;
+use crate::params::ProblemFormat;
+use crate::params::PARAMS_EXTENSION;
+use bytecode::*;
+use cache::*;
 use common::*;
 use evolution::*;
 use execution::*;
+use fitness::*;
 use growing::*;
+use selection::*;
+use syntax::*;
+use validation::*;
 
 use rand::prelude::*;
 use rand::SeedableRng;
+use rayon::prelude::*;
+use rayon::ThreadPool;
 use std::cell::RefCell;
+use std::collections::HashSet;
 use std::error::Error;
 use std::fs;
 use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
 
 pub struct TinyGP {
     rand: StdRng,
@@ -27,7 +51,24 @@ pub struct TinyGP {
     generation: i32,
     population: Vec<Program>,
     fitness: Vec<f32>,
+    // per-case results of every individual
+    evaluations: Vec<Evaluation>,
+    // compiled form of every individual, shared with the cache
+    compiled: Vec<Arc<Bytecode>>,
+    // what the selection scheme knows about the population at the start of the generation
+    prepared: Prepared,
     writer: RefCell<Box<dyn Write>>,
+    pool: ThreadPool,
+    cache: FitnessCache,
+    bloat: BloatStats,
+}
+
+// bloat control events of the current generation
+#[derive(Default)]
+struct BloatStats {
+    tarpeian_rejections: usize,
+    oversized_offspring: usize,
+    size_picks: usize,
 }
 
 impl TinyGP {
@@ -37,33 +78,57 @@ impl TinyGP {
         seed: Option<u64>,
         writer: RefCell<Box<dyn Write>>,
     ) -> TinyGP {
-        let seed = seed.unwrap_or(StdRng::from_entropy().next_u64());
+        let seed = seed
+            .or(params.seed)
+            .unwrap_or(StdRng::from_entropy().next_u64());
         let mut rand = StdRng::seed_from_u64(seed);
-        params.seed = seed;
+        params.seed = Some(seed);
+        // 0 threads lets rayon use every core
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(params.threads)
+            .build()
+            .expect("Cannot start the evaluation threads");
         writeln!(writer.borrow_mut(), "Creating variables").unwrap();
+        if params.memory_init == MemoryInit::Random {
+            // drawn once, so that every case and every program starts from the same memory
+            while params.constants.len() < params.memsize {
+                let constant = rand.gen_range(params.min_random, params.max_random);
+                params.constants.push(constant);
+            }
+        }
         writeln!(writer.borrow_mut(), "Creating population").unwrap();
-        let (population, fitness) = random_population(&params, &cases, &mut rand);
-        TinyGP {
+        let population = random_population(&params, &mut rand);
+        let cache = FitnessCache::new(params.cache_size);
+        let mut tgp = TinyGP {
             rand,
-            fitness,
-            population,
+            fitness: Vec::new(),
+            evaluations: Vec::new(),
+            compiled: Vec::new(),
+            prepared: Prepared::default(),
+            population: Vec::new(),
             params,
             cases,
             generation: 0,
-            writer: writer.into(),
-        }
+            writer,
+            pool,
+            cache,
+            bloat: BloatStats::default(),
+        };
+        (tgp.fitness, tgp.evaluations, tgp.compiled) = tgp.evaluate(&population);
+        tgp.population = population;
+        tgp.prepare_selection();
+        tgp
     }
 
     pub fn from_problem(
         filename: &str,
+        format: Option<ProblemFormat>,
         seed: Option<u64>,
         writer: Box<dyn Write>,
+        configure: &dyn Fn(&mut Params) -> Result<(), String>,
     ) -> Result<TinyGP, Box<dyn Error>> {
-        let content = fs::read_to_string(filename)?;
         let writer = RefCell::new(writer);
-        writeln!(*writer.borrow_mut(), "{content}").unwrap();
-        let (params, cases) = Params::from_string(content)?;
-        writeln!(*writer.borrow_mut(), "{}", cases.len()).unwrap();
+        let (params, cases) = load_problem(filename, format, &mut *writer.borrow_mut(), configure)?;
         Ok(TinyGP::new(params, cases, seed, writer))
     }
 
@@ -86,7 +151,7 @@ impl TinyGP {
 
         if best_fitness >= self.params.acceptable_error {
             writeln!(self.writer.borrow_mut(), "PROBLEM SOLVED").unwrap();
-            fs::write("solution.txt", format!("{:?}", self.population[best_id])).unwrap();
+            fs::write("solution.txt", pprint(&self.population[best_id])).unwrap();
         } else {
             writeln!(self.writer.borrow_mut(), "PROBLEM UNSOLVED").unwrap();
         }
@@ -94,31 +159,197 @@ impl TinyGP {
     }
 
     fn evolve_generation(&mut self) {
-        for _ in 0..self.params.popsize {
-            let child_program: Program;
-            if self.rand.gen_bool(self.params.crossover_prob as f64) {
-                let father_id =
-                    tournament(&self.fitness, self.params.tournament_size, &mut self.rand);
-                let mother_id =
-                    tournament(&self.fitness, self.params.tournament_size, &mut self.rand);
-                let father = &self.population[father_id];
-                let mother = &self.population[mother_id];
-                child_program = crossover(father, mother, &mut self.rand);
-            } else {
-                let parent_id =
-                    tournament(&self.fitness, self.params.tournament_size, &mut self.rand);
-                let parent = &self.population[parent_id];
-                child_program = mutation(parent, &self.params, &mut self.rand);
-            };
+        self.bloat = BloatStats::default();
+        self.prepare_selection();
+        match self.params.evolution {
+            Evolution::SteadyState => self.steady_state_generation(),
+            Evolution::Generational => self.generational_generation(),
+        }
+        self.generation += 1;
+    }
+
+    // children are bred and evaluated in batches of EVAL_BATCH, a batch of 1 is plain steady-state
+    fn steady_state_generation(&mut self) {
+        let mut bred = 0;
+        while bred < self.params.popsize {
+            let batch = self.params.eval_batch.clamp(1, self.params.popsize - bred);
+            let children: Vec<Program> = (0..batch).map(|_| self.breed()).collect();
+            self.replace_losers(children);
+            bred += batch;
+        }
+    }
+
+    // every child replaces the loser of a negative tournament, the children can be picked as
+    // parents right away
+    fn replace_losers(&mut self, children: Vec<Program>) {
+        let (fitness, evaluations, compiled) = self.evaluate(&children);
+        for (((child_program, child_fitness), child_evaluation), child_compiled) in children
+            .into_iter()
+            .zip(fitness)
+            .zip(evaluations)
+            .zip(compiled)
+        {
             let child_index =
                 negative_tournament(&self.fitness, self.params.tournament_size, &mut self.rand);
-            self.fitness[child_index] =
-                fitness_func(&child_program, &self.params, &self.cases);
+            self.fitness[child_index] = child_fitness;
+            self.evaluations[child_index] = child_evaluation;
+            self.compiled[child_index] = child_compiled;
             self.population[child_index] = child_program;
         }
-        self.generation += 1;
+        self.prepare_selection();
+    }
+
+    // the ELITISM best individuals are copied over, the rest of the population is bred from scratch
+    fn generational_generation(&mut self) {
+        let popsize = self.population.len();
+        let mut ranking: Vec<usize> = (0..popsize).collect();
+        ranking.sort_by(|&a, &b| rank_key(self.fitness[b]).total_cmp(&rank_key(self.fitness[a])));
+        let elite = self.params.elitism.min(popsize);
+
+        let children: Vec<Program> = (elite..popsize).map(|_| self.breed()).collect();
+        let (children_fitness, children_evaluations, children_compiled) = self.evaluate(&children);
+
+        let mut population = Vec::with_capacity(popsize);
+        let mut fitness = Vec::with_capacity(popsize);
+        let mut evaluations = Vec::with_capacity(popsize);
+        let mut compiled = Vec::with_capacity(popsize);
+        for &i in &ranking[..elite] {
+            population.push(self.population[i].clone());
+            fitness.push(self.fitness[i]);
+            evaluations.push(self.evaluations[i].clone());
+            compiled.push(Arc::clone(&self.compiled[i]));
+        }
+        population.extend(children);
+        fitness.extend(children_fitness);
+        evaluations.extend(children_evaluations);
+        compiled.extend(children_compiled);
+        self.population = population;
+        self.fitness = fitness;
+        self.evaluations = evaluations;
+        self.compiled = compiled;
+    }
+
+    fn breed(&mut self) -> Program {
+        let (parent_id, child) = if self.rand.gen_bool(self.params.crossover_prob as f64) {
+            let father_id = self.select();
+            let mother_id = self.select();
+            let father = &self.population[father_id];
+            let mother = &self.population[mother_id];
+            (
+                father_id,
+                crossover(father, mother, &self.params, &mut self.rand),
+            )
+        } else {
+            let parent_id = self.select();
+            let parent = &self.population[parent_id];
+            (parent_id, mutation(parent, &self.params, &mut self.rand))
+        };
+        if child.len() > self.params.max_program_len || required_depth(&child) > self.params.depth {
+            log::debug!("offspring exceeds the size limits, copying the parent");
+            self.bloat.oversized_offspring += 1;
+            return self.population[parent_id].clone();
+        }
+        child
+    }
+
+    // fitness, evaluation and compiled form of every program, programs missing from the cache are
+    // compiled and run in parallel, case results are collected in case order so the fitness
+    // doesn't depend on the number of threads
+    fn evaluate(
+        &mut self,
+        programs: &[Program],
+    ) -> (Vec<f32>, Vec<Evaluation>, Vec<Arc<Bytecode>>) {
+        let avg_len = average_len(&self.population);
+        let mut known: Vec<Option<Scored>> = Vec::with_capacity(programs.len());
+        for program in programs {
+            // Tarpeian method, the empty initial population has no average to compare against
+            let rejected = self.params.tarpeian_prob > 0.0
+                && !self.population.is_empty()
+                && program.len() as f32 > avg_len
+                && self
+                    .rand
+                    .gen_bool(self.params.tarpeian_prob.min(1.0) as f64);
+            if rejected {
+                self.bloat.tarpeian_rejections += 1;
+                let compiled = Arc::new(compile(program));
+                known.push(Some((f32::NEG_INFINITY, Evaluation::rejected(), compiled)));
+            } else {
+                known.push(self.cache.get(program));
+            }
+        }
+        let evaluated: Vec<bool> = known.iter().map(Option::is_none).collect();
+        let (params, cases) = (&self.params, &self.cases);
+        let scored: Vec<Scored> = self.pool.install(|| {
+            programs
+                .par_iter()
+                .zip(known)
+                .map(|(program, known)| {
+                    known.unwrap_or_else(|| {
+                        let compiled = Arc::new(compile(program));
+                        let evaluation = evaluate_program(&compiled, params, cases);
+                        let fitness = -evaluation.error - params.parsimony * program.len() as f32;
+                        log::trace!("the fitness is: {fitness}");
+                        (fitness, evaluation, compiled)
+                    })
+                })
+                .collect()
+        });
+        let mut fitness = Vec::with_capacity(programs.len());
+        let mut evaluations = Vec::with_capacity(programs.len());
+        let mut compiled = Vec::with_capacity(programs.len());
+        for ((program, (program_fitness, evaluation, bytecode)), evaluated) in
+            programs.iter().zip(scored).zip(evaluated)
+        {
+            if evaluated {
+                self.cache
+                    .insert(program, program_fitness, &evaluation, &bytecode);
+            }
+            fitness.push(program_fitness);
+            evaluations.push(evaluation);
+            compiled.push(bytecode);
+        }
+        (fitness, evaluations, compiled)
     }
 
+    // with SIZE_TOURNAMENT_PROB the shorter of two parents picked by fitness is preferred
+    fn select(&mut self) -> usize {
+        let first = self.select_by_fitness();
+        if self.params.size_tournament_prob <= 0.0 {
+            return first;
+        }
+        let second = self.select_by_fitness();
+        let (shorter, longer) = if self.population[second].len() < self.population[first].len() {
+            (second, first)
+        } else {
+            (first, second)
+        };
+        if !self
+            .rand
+            .gen_bool(self.params.size_tournament_prob.min(1.0) as f64)
+        {
+            return longer;
+        }
+        if self.population[shorter].len() < self.population[longer].len() {
+            self.bloat.size_picks += 1;
+        }
+        shorter
+    }
+
+    fn select_by_fitness(&mut self) -> usize {
+        selection(self.params.selection).select(
+            &self.fitness,
+            &self.evaluations,
+            &self.prepared,
+            &self.params,
+            &mut self.rand,
+        )
+    }
+
+    fn prepare_selection(&mut self) {
+        self.prepared = selection(self.params.selection).prepare(&self.fitness, &self.evaluations);
+    }
+
+    // fitness is reported and checked against ACCEPTABLE_ERROR without the parsimony penalty
     fn stats(&mut self) -> (f32, usize) {
         let mut best = 0;
         let mut node_count = 0;
@@ -128,74 +359,608 @@ impl TinyGP {
 
         for i in 0..popsize {
             node_count += self.population[i].len();
-            avg_fitness += self.fitness[i];
-            if self.fitness[i] > best_fitness {
+            let fitness = -self.evaluations[i].error;
+            avg_fitness += fitness;
+            if fitness > best_fitness {
                 best = i;
-                best_fitness = self.fitness[i];
+                best_fitness = fitness;
             }
         }
         let avg_len = node_count / popsize;
         avg_fitness /= popsize as f32;
+        let runs = (popsize * self.cases.len()).max(1);
+        let steps: usize = self.evaluations.iter().map(Evaluation::steps).sum();
+        let count = |termination| -> usize {
+            self.evaluations
+                .iter()
+                .map(|evaluation| evaluation.count(termination))
+                .sum()
+        };
 
         writeln!(
             self.writer.borrow_mut(),
             "Generation={}
 Avg Fitness={}
 Best Fitness={}
-Avg Size={}",
+Avg Size={}
+Avg Steps={}
+Max Iteration Runs={}
+Semantic Error Runs={}
+Step Limit Runs={}
+Output Limit Runs={}
+Cache Hit Rate={:.1}%
+Avg Parsimony Penalty={}
+Tarpeian Rejections={}
+Oversized Offspring={}
+Size Tournament Picks={}",
             self.generation,
             -avg_fitness,
             -best_fitness,
-            avg_len
+            avg_len,
+            steps / runs,
+            count(Termination::MaxIteration),
+            count(Termination::Semantic),
+            count(Termination::StepLimit),
+            count(Termination::OutputLimit),
+            self.cache.hit_rate() * 100.0,
+            self.params.parsimony * average_len(&self.population),
+            self.bloat.tarpeian_rejections,
+            self.bloat.oversized_offspring,
+            self.bloat.size_picks
         )
         .unwrap();
         writeln!(self.writer.borrow_mut(), "Best Individual: ").unwrap();
-        // writeln!(self.writer.borrow_mut(), "{:?}", self.population[best]);
-        // pprint(&self.population[best]);
-        writeln!(self.writer.borrow_mut(), "{:?}\n", &self.population[best]).unwrap();
+        writeln!(
+            self.writer.borrow_mut(),
+            "{}\n",
+            pprint(&self.population[best])
+        )
+        .unwrap();
 
         (best_fitness, best)
     }
 }
 
-fn create_random_indiv(params: &Params, rand: &mut StdRng) -> Program {
-    let mut program: Program = Vec::with_capacity(2 * params.depth);
-    grow_stat(&mut program, params.depth, params, rand);
-    grow_stat(&mut program, params.depth, params, rand);
-    program
+fn load_problem(
+    filename: &str,
+    format: Option<ProblemFormat>,
+    writer: &mut dyn Write,
+    configure: &dyn Fn(&mut Params) -> Result<(), String>,
+) -> Result<(Params, Vec<Case>), Box<dyn Error>> {
+    let content = fs::read_to_string(filename)?;
+    writeln!(writer, "{content}").unwrap();
+
+    let mut params = Params::default();
+    let sidecar = Path::new(filename).with_extension(PARAMS_EXTENSION);
+    if sidecar.is_file() {
+        params.read_parameters(&fs::read_to_string(sidecar)?)?;
+    }
+    // overrides are needed to pick CSV columns, and have to win over the problem file
+    configure(&mut params)?;
+    let format = format.or_else(|| ProblemFormat::from_path(filename));
+    let cases = params.read_problem(&content, format)?;
+    configure(&mut params)?;
+
+    writeln!(writer, "{}", cases.len()).unwrap();
+    Ok((params, cases))
 }
 
-fn fitness_func(
-    program: &Program,
-    params: &Params,
-    cases: &Vec<Case>
-) -> f32 {
-    cases.iter().fold(0.0, |acc, (inputs, targets)| {
-        let runtime = Runtime::new(params.memsize, inputs.clone()); // TODO dont clone inputs, not needed
-        let output = execute(program, runtime);
-        let output = output.get(0).unwrap_or(&f32::INFINITY); // FIXME
-        let error = (output - targets[0]).abs();
-        let fitness = acc - error;
-        log::trace!("the fitness is: {fitness}");
-        fitness
-    })
+// runs a program written in the text syntax on the cases of the problem
+pub fn replay(
+    filename: &str,
+    format: Option<ProblemFormat>,
+    solution: &str,
+    old_syntax: bool,
+    mut writer: Box<dyn Write>,
+    configure: &dyn Fn(&mut Params) -> Result<(), String>,
+) -> Result<(), Box<dyn Error>> {
+    let (params, cases) = load_problem(filename, format, &mut writer, configure)?;
+    let program = if old_syntax {
+        let inputs = cases.first().map_or(0, |(inputs, _)| inputs.len());
+        dialect::compile(solution, inputs)?
+    } else {
+        parse(solution)?
+    };
+    // registers past the memory are only a mistake when they stop the run
+    let memsize = match params.out_of_range {
+        OutOfRange::Error => params.memsize,
+        OutOfRange::Wrap | OutOfRange::Zero => usize::MAX,
+    };
+    if let Err(errors) = validate(&program, memsize) {
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        return Err(format!("Invalid program:\n{}", errors.join("\n")).into());
+    }
+    let bytecode = compile(&program);
+    let evaluation = evaluate_program(&bytecode, &params, &cases);
+
+    writeln!(writer, "{}\n", pprint(&program))?;
+    for ((inputs, targets), result) in cases.iter().zip(&evaluation.cases) {
+        // ran again for the details the evaluation doesn't keep
+        let report = bytecode.run(Runtime::with_params(&params, inputs.clone()));
+        let stop = match &report.error {
+            None => "Ran to the end".to_owned(),
+            Some(EvalError::Finished) => "Ran out of input".to_owned(),
+            Some(error) => format!("{error:?}"),
+        };
+        writeln!(
+            writer,
+            "{inputs:?} -> {:?} expected {targets:?}, error={} ({stop} after {} steps, memory {:?})",
+            result.output, result.error, result.steps, report.memory
+        )?;
+    }
+    writeln!(writer, "Fitness={}", -evaluation.error)?;
+    writer.flush()?;
+    Ok(())
 }
 
-fn random_population(
+// how many times a duplicate individual is regrown at one depth before going one level deeper
+const MAX_DUPLICATE_RETRIES: usize = 10;
+
+fn create_random_indiv(
     params: &Params,
-    cases: &Vec<Case>,
+    depth: usize,
+    method: Method,
     rand: &mut StdRng,
-) -> (Vec<Program>, Vec<f32>) {
+) -> Program {
+    let mut program: Program = Vec::with_capacity(2 * depth);
+    grow_block(&mut program, depth, method, params, rand);
+    program
+}
+
+// depth and growing method of the i-th individual of the initial population
+fn initial_shape(params: &Params, i: usize) -> (usize, Method) {
+    match params.initialization {
+        Initialization::Full => (params.depth, Method::Full),
+        Initialization::Grow => (params.depth, Method::Grow),
+        Initialization::RampedHalfAndHalf => {
+            let min_depth = params.depth.min(2);
+            let depth = min_depth + (i / 2) % (params.depth - min_depth + 1);
+            let method = if i.is_multiple_of(2) {
+                Method::Full
+            } else {
+                Method::Grow
+            };
+            (depth, method)
+        }
+    }
+}
+
+// runs the compiled program on every case, errors are measured with the metric from params
+fn evaluate_program(bytecode: &Bytecode, params: &Params, cases: &[Case]) -> Evaluation {
+    let metric = metric(params.metric);
+    let cases = cases
+        .par_iter()
+        .map(|(inputs, targets)| {
+            let runtime = Runtime::with_params(params, inputs.clone()); // TODO dont clone inputs, not needed
+            let report = bytecode.run(runtime);
+            let termination = report.termination();
+            CaseResult {
+                error: metric.case_error(&report.output, targets, params)
+                    + termination_penalty(termination, params),
+                output: report.output,
+                termination,
+                steps: report.steps,
+            }
+        })
+        .collect();
+    let mut evaluation = Evaluation { error: 0.0, cases };
+    evaluation.error = metric.aggregate(&evaluation.errors());
+    evaluation
+}
+
+// negated error of the program under the metric from params
+#[cfg(test)]
+fn fitness_func(program: &Program, params: &Params, cases: &[Case]) -> f32 {
+    -evaluate_program(&compile(program), params, cases).error
+}
+
+fn average_len(population: &[Program]) -> f32 {
+    let total: usize = population.iter().map(Vec::len).sum();
+    total as f32 / population.len().max(1) as f32
+}
+
+// duplicates are regrown, deeper when they keep repeating, and only kept once even programs as
+// deep as params.depth allows repeat
+fn random_population(params: &Params, rand: &mut StdRng) -> Vec<Program> {
     let mut population = Vec::with_capacity(params.popsize);
-    let mut fitness = Vec::with_capacity(params.popsize);
+    let mut seen = HashSet::with_capacity(params.popsize);
+    let mut duplicates = 0;
 
     for i in 0..params.popsize {
-        population.push(create_random_indiv(params, rand));
-        fitness.push(fitness_func(&population[i], params, cases));
+        let (mut depth, method) = initial_shape(params, i);
+        let mut program = create_random_indiv(params, depth, method, rand);
+        let mut retries = 0;
+        while !seen.insert(structural_hash(&program)) {
+            if retries == MAX_DUPLICATE_RETRIES {
+                if depth >= params.depth {
+                    duplicates += 1;
+                    break;
+                }
+                depth += 1;
+                retries = 0;
+            }
+            log::debug!("regrowing duplicate individual {i} at depth {depth}");
+            program = create_random_indiv(params, depth, method, rand);
+            retries += 1;
+        }
+        population.push(program);
     }
 
-    return (population, fitness);
+    if duplicates > 0 {
+        log::warn!("kept {duplicates} duplicate individuals in the initial population");
+    }
+    population
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+    use crate::params::{Metric, SelectionScheme};
+
+    // cases mapping the inputs (i, 2) to target(i)
+    fn cases(n: usize, target: impl Fn(f32) -> f32) -> Vec<Case> {
+        (0..n)
+            .map(|i| (vec![i as f32, 2.0], vec![target(i as f32)]))
+            .collect()
+    }
+
+    // a run whose reports go nowhere
+    fn quiet_gp(params: Params, cases: Vec<Case>, seed: u64) -> TinyGP {
+        let writer: Box<dyn Write> = Box::new(std::io::sink());
+        TinyGP::new(params, cases, Some(seed), RefCell::new(writer))
+    }
+
+    #[test]
+    fn test_ramped_half_and_half_population() {
+        let params = Params {
+            memsize: 2,
+            popsize: 40,
+            depth: 4,
+            ..Default::default()
+        };
+        let mut rand = StdRng::seed_from_u64(0);
+        let population = random_population(&params, &mut rand);
+        assert_eq!(population.len(), params.popsize);
+        for (i, program) in population.iter().enumerate() {
+            assert!(!population[..i].contains(program), "duplicate at {i}");
+        }
+        let shapes: Vec<(usize, Method)> = (0..params.popsize)
+            .map(|i| initial_shape(&params, i))
+            .collect();
+        for depth in 2..=params.depth {
+            assert!(shapes.contains(&(depth, Method::Full)));
+            assert!(shapes.contains(&(depth, Method::Grow)));
+        }
+    }
+
+    #[test]
+    fn test_population_of_a_small_search_space() {
+        // a handful of single statements over one register and one constant
+        let params = Params {
+            memsize: 1,
+            popsize: 300,
+            depth: 1,
+            initialization: Initialization::Grow,
+            min_random: 0.0,
+            max_random: 0.0,
+            ..Default::default()
+        };
+        let mut rand = StdRng::seed_from_u64(0);
+        let population = random_population(&params, &mut rand);
+        assert_eq!(population.len(), params.popsize);
+        let distinct: HashSet<u64> = population.iter().map(|p| structural_hash(p)).collect();
+        // there are only 155 different programs to grow
+        assert!(distinct.len() <= 155);
+        assert!(distinct.len() > 100, "{}", distinct.len());
+    }
+
+    #[test]
+    fn test_fitness_squares() {
+        let params = Params {
+            memsize: 2,
+            ..Default::default()
+        };
+        #[rustfmt::skip]
+        let squares = vec![
+            Token::Stat(Stat::INPUT), Token::Reg(0),
+            Token::Stat(Stat::WHILE), Token::Reg(0),
+                Token::Stat(Stat::LOAD), Token::Reg(1),
+                    Token::Expr(Expr::ADD), Token::Reg(1), Token::Expr(Expr::NUM(1.0)),
+                Token::Stat(Stat::OUTPUT),
+                    Token::Expr(Expr::MUL), Token::Reg(1), Token::Reg(1),
+                Token::Stat(Stat::LOAD), Token::Reg(0),
+                    Token::Expr(Expr::SUB), Token::Reg(0), Token::Expr(Expr::NUM(1.0)),
+            Token::END,
+        ];
+        let cases = vec![(vec![1.0], vec![1.0]), (vec![3.0], vec![1.0, 4.0, 9.0])];
+        assert_eq!(fitness_func(&squares, &params, &cases), 0.0);
+        let cases = vec![(vec![2.0], vec![1.0, 4.0, 9.0])];
+        assert_eq!(
+            fitness_func(&squares, &params, &cases),
+            -params.missing_output_penalty
+        );
+    }
+
+    #[test]
+    fn test_fitness_follows_metric() {
+        #[rustfmt::skip]
+        let program = vec![
+            Token::Stat(Stat::OUTPUT), Token::Expr(Expr::NUM(1.0)),
+        ];
+        let cases = vec![(vec![], vec![3.0]), (vec![], vec![1.0])];
+        let mut params = Params {
+            memsize: 1,
+            ..Default::default()
+        };
+        assert_eq!(fitness_func(&program, &params, &cases), -2.0);
+        params.metric = Metric::MeanSquared;
+        assert_eq!(fitness_func(&program, &params, &cases), -2.0);
+        params.metric = Metric::Rmse;
+        assert_eq!(fitness_func(&program, &params, &cases), -(2.0f32.sqrt()));
+        params.metric = Metric::MaxError;
+        assert_eq!(fitness_func(&program, &params, &cases), -2.0);
+        params.metric = Metric::HitCount;
+        assert_eq!(fitness_func(&program, &params, &cases), -1.0);
+        params.metric = Metric::RelativeError;
+        assert_eq!(fitness_func(&program, &params, &cases), -1.0 / 3.0);
+    }
+
+    #[test]
+    fn test_failures_are_penalised() {
+        #[rustfmt::skip]
+        let crashing = vec![
+            Token::Stat(Stat::OUTPUT), Token::Expr(Expr::NUM(3.0)),
+            Token::Stat(Stat::OUTPUT), Token::Reg(5),
+        ];
+        #[rustfmt::skip]
+        let looping = vec![
+            Token::Stat(Stat::OUTPUT), Token::Expr(Expr::NUM(3.0)),
+            Token::Stat(Stat::WHILE), Token::Expr(Expr::NUM(1.0)), Token::END,
+        ];
+        let cases = vec![(vec![], vec![3.0])];
+        let mut params = Params {
+            memsize: 1,
+            ..Default::default()
+        };
+        assert_eq!(fitness_func(&crashing, &params, &cases), f32::NEG_INFINITY);
+        assert_eq!(fitness_func(&looping, &params, &cases), 0.0);
+        params.semantic_error_penalty = 10.0;
+        params.max_iteration_penalty = 2.0;
+        assert_eq!(fitness_func(&crashing, &params, &cases), -10.0);
+        assert_eq!(fitness_func(&looping, &params, &cases), -2.0);
+        // the output written before the crash still counts
+        assert_eq!(
+            evaluate_program(&compile(&crashing), &params, &cases).cases[0].output,
+            vec![3.0]
+        );
+    }
+
+    #[test]
+    fn test_deterministic_across_thread_counts() {
+        let run = |threads: usize, eval_batch: usize| {
+            let params = Params {
+                memsize: 2,
+                popsize: 30,
+                threads,
+                eval_batch,
+                ..Default::default()
+            };
+            let mut tgp = quiet_gp(params, cases(50, |i| i * i), 7);
+            for _ in 0..5 {
+                tgp.evolve_generation();
+            }
+            (tgp.population, tgp.fitness)
+        };
+        for eval_batch in [1, 8] {
+            let single = run(1, eval_batch);
+            assert_eq!(single, run(4, eval_batch));
+            assert_eq!(single, run(0, eval_batch));
+        }
+    }
+
+    #[test]
+    fn test_random_memory_is_drawn_once() {
+        let params = Params {
+            memsize: 4,
+            popsize: 10,
+            memory_init: MemoryInit::Random,
+            constants: vec![0.5],
+            min_random: -2.0,
+            max_random: 2.0,
+            ..Default::default()
+        };
+        let tgp = quiet_gp(params, cases(1, |_| 1.0), 1);
+        let constants = &tgp.params.constants;
+        assert_eq!(constants.len(), 4);
+        assert_eq!(constants[0], 0.5);
+        assert!(constants.iter().all(|c| (-2.0..2.0).contains(c)));
+    }
+
+    #[test]
+    fn test_evaluations_follow_population() {
+        let params = Params {
+            memsize: 2,
+            popsize: 20,
+            ..Default::default()
+        };
+        let mut tgp = quiet_gp(params, cases(10, |i| i * 2.0), 5);
+        for _ in 0..3 {
+            tgp.evolve_generation();
+        }
+        assert_eq!(tgp.evaluations.len(), tgp.population.len());
+        assert_eq!(tgp.compiled.len(), tgp.population.len());
+        for (((program, evaluation), fitness), compiled) in tgp
+            .population
+            .iter()
+            .zip(&tgp.evaluations)
+            .zip(&tgp.fitness)
+            .zip(&tgp.compiled)
+        {
+            assert_eq!(
+                *evaluation,
+                evaluate_program(&compile(program), &tgp.params, &tgp.cases)
+            );
+            assert_eq!(
+                *evaluation,
+                evaluate_program(compiled, &tgp.params, &tgp.cases)
+            );
+            assert_eq!(-evaluation.errors().iter().sum::<f32>(), *fitness);
+            assert!(evaluation.cases.iter().all(|case| case.steps > 0));
+        }
+    }
+
+    #[test]
+    fn test_cache_skips_seen_programs() {
+        let params = Params {
+            memsize: 2,
+            popsize: 20,
+            ..Default::default()
+        };
+        let cases = cases(10, |i| i * 2.0);
+        let mut tgp = quiet_gp(params.clone(), cases.clone(), 5);
+        let population = tgp.population.clone();
+        assert_eq!(tgp.cache.hit_rate(), 0.0);
+        let (fitness, evaluations, compiled) = tgp.evaluate(&population);
+        assert_eq!(fitness, tgp.fitness);
+        assert_eq!(evaluations, tgp.evaluations);
+        assert_eq!(tgp.cache.hit_rate(), 0.5);
+        // cached programs aren't compiled again
+        assert!(compiled
+            .iter()
+            .zip(&tgp.compiled)
+            .all(|(a, b)| Arc::ptr_eq(a, b)));
+
+        // the cache must not change the course of evolution
+        let uncached = Params {
+            cache_size: 0,
+            ..params
+        };
+        let mut reference = quiet_gp(uncached, cases, 5);
+        for _ in 0..5 {
+            tgp.evolve_generation();
+            reference.evolve_generation();
+        }
+        assert_eq!(tgp.population, reference.population);
+        assert_eq!(tgp.fitness, reference.fitness);
+        assert!(tgp.cache.hit_rate() > 0.0);
+    }
+
+    #[test]
+    fn test_bloat_control() {
+        let gp = |params: Params| quiet_gp(params, cases(10, |i| i * 2.0), 9);
+        let params = Params {
+            memsize: 2,
+            popsize: 30,
+            ..Default::default()
+        };
+
+        let mut tgp = gp(Params {
+            parsimony: 0.5,
+            ..params.clone()
+        });
+        tgp.evolve_generation();
+        for i in 0..tgp.population.len() {
+            let penalty = 0.5 * tgp.population[i].len() as f32;
+            assert_eq!(tgp.fitness[i], -tgp.evaluations[i].error - penalty);
+        }
+
+        let mut tgp = gp(Params {
+            tarpeian_prob: 1.0,
+            ..params.clone()
+        });
+        let avg_len = average_len(&tgp.population);
+        let children = tgp.population.clone();
+        let (fitness, evaluations, _) = tgp.evaluate(&children);
+        assert!(tgp.bloat.tarpeian_rejections > 0);
+        for ((child, fitness), evaluation) in children.iter().zip(fitness).zip(evaluations) {
+            let rejected = child.len() as f32 > avg_len;
+            assert_eq!(fitness == f32::NEG_INFINITY, rejected);
+            assert_eq!(evaluation == Evaluation::rejected(), rejected);
+        }
+
+        let mut tgp = gp(Params {
+            max_program_len: 20,
+            ..params.clone()
+        });
+        let parents = tgp.population.clone();
+        for _ in 0..100 {
+            let child = tgp.breed();
+            assert!(child.len() <= 20 || parents.contains(&child));
+        }
+        assert!(tgp.bloat.oversized_offspring > 0);
+
+        let mut tgp = gp(Params {
+            size_tournament_prob: 1.0,
+            ..params
+        });
+        let avg_len = average_len(&tgp.population);
+        let picked: Vec<usize> = (0..200).map(|_| tgp.select()).collect();
+        assert!(tgp.bloat.size_picks > 0);
+        assert!(
+            average_len(
+                &picked
+                    .iter()
+                    .map(|&i| tgp.population[i].clone())
+                    .collect::<Vec<_>>()
+            ) < avg_len
+        );
+    }
+
+    #[test]
+    fn test_replaced_children_are_picked_by_their_fitness() {
+        // multiplying the two inputs solves every case
+        #[rustfmt::skip]
+        let solution = vec![
+            Token::Stat(Stat::INPUT), Token::Reg(0),
+            Token::Stat(Stat::INPUT), Token::Reg(1),
+            Token::Stat(Stat::OUTPUT), Token::Expr(Expr::MUL), Token::Reg(0), Token::Reg(1),
+        ];
+        for selection in [SelectionScheme::Roulette, SelectionScheme::Rank] {
+            let params = Params {
+                memsize: 2,
+                popsize: 20,
+                selection,
+                ..Default::default()
+            };
+            let mut tgp = quiet_gp(params, cases(10, |i| i * 2.0), 4);
+            tgp.replace_losers(vec![solution.clone()]);
+            let child = tgp.population.iter().position(|p| *p == solution).unwrap();
+            assert_eq!(tgp.fitness[child], 0.0);
+            let picks = (0..2000)
+                .filter(|_| tgp.select_by_fitness() == child)
+                .count();
+            // more often than a uniform pick out of 20
+            assert!(picks > 100, "{selection:?} picked the child {picks} times");
+        }
+    }
+
+    #[test]
+    fn test_generational_keeps_elite() {
+        let params = Params {
+            memsize: 2,
+            popsize: 20,
+            evolution: Evolution::Generational,
+            elitism: 3,
+            ..Default::default()
+        };
+        let mut tgp = quiet_gp(params, cases(10, |i| i + 2.0), 3);
+        for _ in 0..10 {
+            let mut best: Vec<(f32, Program)> = tgp
+                .fitness
+                .iter()
+                .copied()
+                .zip(tgp.population.iter().cloned())
+                .collect();
+            best.sort_by(|a, b| b.0.total_cmp(&a.0));
+            best.truncate(3);
+
+            tgp.evolve_generation();
+            assert_eq!(tgp.population.len(), 20);
+            assert_eq!(tgp.fitness.len(), 20);
+            for (fitness, program) in best {
+                let kept = tgp.population.iter().position(|p| *p == program).unwrap();
+                assert_eq!(tgp.fitness[kept], fitness);
+            }
+        }
+    }
+}
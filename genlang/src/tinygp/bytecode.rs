@@ -0,0 +1,401 @@
+// programs compiled to a flat list of instructions: expressions are evaluated on a stack
+// in postfix order and blocks are entered and left with jumps to precomputed targets,
+// so running a program never has to search for the ELSE or END closing a block
+//
+// INPUT R0                 0: Input(0)
+// WHILE GT R0 0 DO         1: While(0)
+//                          2: Reg(0)  3: Num(0)  4: Binary(GT)  5: JumpUnless(14)
+//   LOAD R0 SUB R0 1       6: Reg(0)  7: Num(1)  8: Binary(SUB)  9: Load(0)
+// END                      10: Reg(0)  11: Num(0)  12: Binary(GT)  13: Loop { level: 0, body: 6 }
+
+use super::common::*;
+use super::execution::{is_truthy, operator, EvalError, ExecutionReport, Operator, Runtime};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Op {
+    Num(f32),
+    Reg(usize),
+    Unary(fn(f32) -> f32),
+    Binary(fn(f32, f32) -> f32),
+    Input(usize),
+    Load(usize),
+    Output,
+    // pops the condition of an IF, jumps to the ELSE branch or past the END when it is false
+    If(usize),
+    // starts a WHILE at the given nesting level, resetting its iteration count
+    While(usize),
+    JumpUnless(usize),
+    Jump(usize),
+    // pops the condition re-evaluated after the body, counts the iteration and jumps back while it holds
+    Loop { level: usize, body: usize },
+}
+
+#[derive(Debug, Clone)]
+pub struct Bytecode {
+    pub ops: Vec<Op>,
+    // deepest nesting of WHILE loops, each level keeps its own iteration count
+    loop_levels: usize,
+    // the most values an expression keeps on the stack at once
+    stack_size: usize,
+}
+
+pub fn compile(program: &Program) -> Bytecode {
+    let mut compiler = Compiler {
+        program,
+        ops: Vec::with_capacity(program.len()),
+        level: 0,
+        loop_levels: 0,
+        stack: 0,
+        stack_size: 0,
+    };
+    compiler.block(0);
+    Bytecode {
+        ops: compiler.ops,
+        loop_levels: compiler.loop_levels,
+        stack_size: compiler.stack_size,
+    }
+}
+
+struct Compiler<'a> {
+    program: &'a Program,
+    ops: Vec<Op>,
+    // WHILE loops enclosing the compiled statement
+    level: usize,
+    loop_levels: usize,
+    stack: usize,
+    stack_size: usize,
+}
+
+impl Compiler<'_> {
+    // compiles statements up to the closing ELSE or END, returns its position
+    fn block(&mut self, pos: usize) -> usize {
+        let mut pos = pos;
+        while pos < self.program.len() && !matches!(self.program[pos], Token::ELSE | Token::END) {
+            pos = self.stat(pos);
+        }
+        pos
+    }
+
+    fn stat(&mut self, pos: usize) -> usize {
+        let Token::Stat(stat) = self.program[pos] else {
+            panic!("Invalid syntax at {pos}: expected a statement");
+        };
+        match stat {
+            Stat::INPUT => {
+                self.emit(Op::Input(self.destination(pos + 1)));
+                pos + 2
+            }
+            Stat::LOAD => {
+                let destination = self.destination(pos + 1);
+                let end = self.expr(pos + 2);
+                self.emit(Op::Load(destination));
+                end
+            }
+            Stat::OUTPUT => {
+                let end = self.expr(pos + 1);
+                self.emit(Op::Output);
+                end
+            }
+            Stat::IF => {
+                let block_pos = self.expr(pos + 1);
+                let branch = self.emit(Op::If(0));
+                let closing = self.block(block_pos);
+                let end = match self.program.get(closing) {
+                    Some(Token::ELSE) => {
+                        let jump = self.emit(Op::Jump(0));
+                        self.ops[branch] = Op::If(self.ops.len());
+                        let end = self.block(closing + 1);
+                        self.ops[jump] = Op::Jump(self.ops.len());
+                        end
+                    }
+                    _ => {
+                        self.ops[branch] = Op::If(self.ops.len());
+                        closing
+                    }
+                };
+                self.expect_end(pos, end);
+                end + 1
+            }
+            Stat::WHILE => {
+                let level = self.level;
+                self.emit(Op::While(level));
+                let block_pos = self.expr(pos + 1);
+                let exit = self.emit(Op::JumpUnless(0));
+                let body = self.ops.len();
+                self.level += 1;
+                self.loop_levels = self.loop_levels.max(self.level);
+                let end = self.block(block_pos);
+                self.level -= 1;
+                self.expr(pos + 1);
+                self.emit(Op::Loop { level, body });
+                self.ops[exit] = Op::JumpUnless(self.ops.len());
+                self.expect_end(pos, end);
+                end + 1
+            }
+        }
+    }
+
+    fn expr(&mut self, pos: usize) -> usize {
+        match self.program[pos] {
+            Token::Expr(Expr::NUM(val)) => {
+                self.emit(Op::Num(val));
+                pos + 1
+            }
+            Token::Expr(expr) => {
+                let mut end = pos + 1;
+                for _ in 0..expr.argnum() {
+                    end = self.expr(end);
+                }
+                match operator(expr) {
+                    Operator::Unary(func) => self.emit(Op::Unary(func)),
+                    Operator::Binary(func) => self.emit(Op::Binary(func)),
+                };
+                end
+            }
+            Token::Reg(num) => {
+                self.emit(Op::Reg(num));
+                pos + 1
+            }
+            token => panic!("Invalid syntax at {pos}: expected an expression, got {token:?}"),
+        }
+    }
+
+    fn destination(&self, pos: usize) -> usize {
+        match self.program.get(pos) {
+            Some(&Token::Reg(num)) => num,
+            _ => panic!("Invalid syntax at {pos}: expected REG"),
+        }
+    }
+
+    fn expect_end(&self, opening: usize, end: usize) {
+        if !matches!(self.program.get(end), Some(Token::END)) {
+            panic!("Invalid syntax at {end}: expected END closing the block started at {opening}");
+        }
+    }
+
+    // appends the instruction and tracks the stack it needs, returns its address
+    fn emit(&mut self, op: Op) -> usize {
+        match op {
+            Op::Num(_) | Op::Reg(_) => self.stack += 1,
+            Op::Binary(_) | Op::Load(_) | Op::Output | Op::If(_) => self.stack -= 1,
+            Op::JumpUnless(_) | Op::Loop { .. } => self.stack -= 1,
+            Op::Unary(_) | Op::Input(_) | Op::While(_) | Op::Jump(_) => (),
+        }
+        self.stack_size = self.stack_size.max(self.stack);
+        self.ops.push(op);
+        self.ops.len() - 1
+    }
+}
+
+impl Bytecode {
+    pub fn run(&self, runtime: Runtime) -> ExecutionReport {
+        let mut runtime = runtime;
+        let error = self.eval(&mut runtime).err();
+        if let Some(EvalError::Semantic(reason)) = &error {
+            log::error!("Invalid program reason: {reason}");
+        }
+        let report = runtime.report(error);
+        log::trace!(
+            "terminated with {:?} and output {:?}",
+            report.error,
+            report.output
+        );
+        report
+    }
+
+    fn eval(&self, runtime: &mut Runtime) -> Result<(), EvalError> {
+        let mut stack: Vec<f32> = Vec::with_capacity(self.stack_size);
+        let mut iterations = vec![0; self.loop_levels];
+        let mut pc = 0;
+        // the compiler balances the stack, so pops never come up empty
+        let pop = |stack: &mut Vec<f32>| stack.pop().unwrap();
+
+        while let Some(&op) = self.ops.get(pc) {
+            pc += 1;
+            match op {
+                Op::Num(val) => {
+                    runtime.step()?;
+                    stack.push(val);
+                }
+                Op::Reg(num) => {
+                    runtime.step()?;
+                    stack.push(runtime.read_reg(num)?);
+                }
+                Op::Unary(func) => {
+                    runtime.step()?;
+                    let arg = pop(&mut stack);
+                    stack.push(func(arg));
+                }
+                Op::Binary(func) => {
+                    runtime.step()?;
+                    let rhs = pop(&mut stack);
+                    let lhs = pop(&mut stack);
+                    stack.push(func(lhs, rhs));
+                }
+                Op::Input(num) => {
+                    runtime.step()?;
+                    let val = runtime.next_input().ok_or(EvalError::Finished)?;
+                    runtime.set_reg(num, val)?;
+                }
+                Op::Load(num) => {
+                    runtime.step()?;
+                    let val = pop(&mut stack);
+                    runtime.set_reg(num, val)?;
+                }
+                Op::Output => {
+                    runtime.step()?;
+                    let val = pop(&mut stack);
+                    runtime.write(val)?;
+                }
+                Op::If(target) => {
+                    runtime.step()?;
+                    if !is_truthy(pop(&mut stack)) {
+                        pc = target;
+                    }
+                }
+                Op::While(level) => {
+                    runtime.step()?;
+                    iterations[level] = 0;
+                }
+                Op::JumpUnless(target) => {
+                    if !is_truthy(pop(&mut stack)) {
+                        pc = target;
+                    }
+                }
+                Op::Jump(target) => pc = target,
+                Op::Loop { level, body } => {
+                    let condition = pop(&mut stack);
+                    iterations[level] += 1;
+                    if iterations[level] >= runtime.max_iterations {
+                        return Err(EvalError::MaxIteration);
+                    }
+                    if is_truthy(condition) {
+                        pc = body;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::execution::reference::interpret;
+    use super::super::growing::random_programs;
+    use super::super::syntax::parse;
+    use super::*;
+    use crate::params::Params;
+    use rand::prelude::*;
+
+    #[test]
+    fn test_jump_targets() {
+        let program = parse("IF R0 OUTPUT 1 ELSE OUTPUT 2 END OUTPUT 3").unwrap();
+        let bytecode = compile(&program);
+        assert!(
+            matches!(
+                bytecode.ops[..],
+                [
+                    Op::Reg(0),
+                    Op::If(5),
+                    Op::Num(_),
+                    Op::Output,
+                    Op::Jump(7),
+                    Op::Num(_),
+                    Op::Output,
+                    Op::Num(_),
+                    Op::Output,
+                ]
+            ),
+            "{:?}",
+            bytecode.ops
+        );
+
+        let program = parse("INPUT R0 WHILE GT R0 0 DO LOAD R0 SUB R0 1 END").unwrap();
+        let bytecode = compile(&program);
+        assert!(matches!(bytecode.ops[5], Op::JumpUnless(14)));
+        assert!(matches!(bytecode.ops[13], Op::Loop { level: 0, body: 6 }));
+        assert_eq!(bytecode.stack_size, 2);
+        assert_eq!(bytecode.loop_levels, 1);
+    }
+
+    #[test]
+    fn test_nested_loops_count_their_own_iterations() {
+        // the inner loop runs 10 times on each of the 20 outer iterations
+        let program = parse(
+            "WHILE LT R0 20 DO
+               LOAD R0 ADD R0 1
+               LOAD R1 0
+               WHILE LT R1 10 DO LOAD R1 ADD R1 1 OUTPUT R1 END
+             END",
+        )
+        .unwrap();
+        let report = compile(&program).run(Runtime::new(2, vec![]));
+        assert_eq!(report.error, None);
+        assert_eq!(report.output.len(), 200);
+        assert_same(
+            &report,
+            &interpret(&program, Runtime::new(2, vec![])),
+            &program,
+        );
+    }
+
+    #[test]
+    fn test_matches_interpreter() {
+        // budgets small enough to cut some of the runs short
+        let params = Params {
+            memsize: 3,
+            depth: 5,
+            max_steps: 500,
+            max_output_len: 20,
+            ..Default::default()
+        };
+        let mut rand = StdRng::seed_from_u64(4);
+        for program in random_programs(4, 2000, &params) {
+            let input: Vec<f32> = (0..rand.gen_range(0, 5))
+                .map(|_| rand.gen_range(-5.0, 5.0))
+                .collect();
+            let expected = interpret(&program, Runtime::with_params(&params, input.clone()));
+            let report = compile(&program).run(Runtime::with_params(&params, input));
+            assert_same(&report, &expected, &program);
+        }
+    }
+
+    #[test]
+    fn test_matches_interpreter_on_every_budget() {
+        // grown with a register past the memory, so that semantic errors race the budget
+        let grown = Params {
+            memsize: 3,
+            depth: 4,
+            ..Default::default()
+        };
+        let mut params = Params {
+            memsize: 2,
+            ..grown.clone()
+        };
+        let mut rand = StdRng::seed_from_u64(6);
+        for program in random_programs(6, 300, &grown) {
+            let input = vec![rand.gen_range(-5.0, 5.0), rand.gen_range(-5.0, 5.0)];
+            let bytecode = compile(&program);
+            params.max_steps = usize::MAX;
+            let steps = bytecode
+                .run(Runtime::with_params(&params, input.clone()))
+                .steps;
+            for max_steps in 0..=steps.min(200) {
+                params.max_steps = max_steps;
+                let expected = interpret(&program, Runtime::with_params(&params, input.clone()));
+                let report = bytecode.run(Runtime::with_params(&params, input.clone()));
+                assert_same(&report, &expected, &program);
+            }
+        }
+    }
+
+    // NaN outputs and registers have to match as well
+    fn assert_same(report: &ExecutionReport, expected: &ExecutionReport, program: &Program) {
+        let bits = |values: &[f32]| values.iter().map(|x| x.to_bits()).collect::<Vec<_>>();
+        assert_eq!(bits(&report.output), bits(&expected.output), "{program:?}");
+        assert_eq!(bits(&report.memory), bits(&expected.memory), "{program:?}");
+        assert_eq!(report.error, expected.error, "{program:?}");
+        assert_eq!(report.steps, expected.steps, "{program:?}");
+    }
+}
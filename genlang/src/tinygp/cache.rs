@@ -0,0 +1,127 @@
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+
+use lru::LruCache;
+
+use super::bytecode::Bytecode;
+use super::common::*;
+use super::fitness::Evaluation;
+
+// fitness, evaluation and compiled form of a program
+pub type Scored = (f32, Evaluation, Arc<Bytecode>);
+
+// fitness and compiled form of recently evaluated programs, keyed by their structural hash
+pub struct FitnessCache {
+    entries: Option<LruCache<u64, Entry>>,
+    lookups: usize,
+    hits: usize,
+}
+
+struct Entry {
+    // kept to tell apart programs with colliding hashes
+    program: Program,
+    fitness: f32,
+    evaluation: Evaluation,
+    bytecode: Arc<Bytecode>,
+}
+
+impl FitnessCache {
+    // a capacity of 0 disables caching
+    pub fn new(capacity: usize) -> Self {
+        FitnessCache {
+            entries: NonZeroUsize::new(capacity).map(LruCache::new),
+            lookups: 0,
+            hits: 0,
+        }
+    }
+
+    pub fn get(&mut self, program: &Program) -> Option<Scored> {
+        let entries = self.entries.as_mut()?;
+        self.lookups += 1;
+        let entry = entries
+            .get(&structural_hash(program))
+            .filter(|entry| same_structure(&entry.program, program))?;
+        self.hits += 1;
+        Some((
+            entry.fitness,
+            entry.evaluation.clone(),
+            Arc::clone(&entry.bytecode),
+        ))
+    }
+
+    pub fn insert(
+        &mut self,
+        program: &Program,
+        fitness: f32,
+        evaluation: &Evaluation,
+        bytecode: &Arc<Bytecode>,
+    ) {
+        if let Some(entries) = self.entries.as_mut() {
+            let entry = Entry {
+                program: program.clone(),
+                fitness,
+                evaluation: evaluation.clone(),
+                bytecode: Arc::clone(bytecode),
+            };
+            entries.put(structural_hash(program), entry);
+        }
+    }
+
+    pub fn hit_rate(&self) -> f32 {
+        if self.lookups == 0 {
+            0.0
+        } else {
+            self.hits as f32 / self.lookups as f32
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::bytecode::compile;
+    use super::*;
+
+    #[test]
+    fn test_cache_hits_and_eviction() {
+        let program = |val| vec![Token::Stat(Stat::OUTPUT), Token::Expr(Expr::NUM(val))];
+        let evaluation = Evaluation::default();
+        let mut cache = FitnessCache::new(2);
+        let insert = |cache: &mut FitnessCache, val, fitness| {
+            let bytecode = Arc::new(compile(&program(val)));
+            cache.insert(&program(val), fitness, &evaluation, &bytecode);
+            bytecode
+        };
+        assert!(cache.get(&program(1.0)).is_none());
+        let bytecode = insert(&mut cache, 1.0, -1.0);
+        insert(&mut cache, 2.0, -2.0);
+        let (fitness, cached, cached_bytecode) = cache.get(&program(1.0)).unwrap();
+        assert_eq!((fitness, cached), (-1.0, evaluation.clone()));
+        // the compiled program is shared, not compiled again
+        assert!(Arc::ptr_eq(&cached_bytecode, &bytecode));
+        // 2.0 is now the least recently used
+        insert(&mut cache, 3.0, -3.0);
+        assert!(cache.get(&program(2.0)).is_none());
+        assert_eq!(cache.get(&program(3.0)).map(|(f, ..)| f), Some(-3.0));
+        assert_eq!(cache.hit_rate(), 0.5);
+    }
+
+    #[test]
+    fn test_cache_hits_nan_constants() {
+        let program = vec![Token::Stat(Stat::OUTPUT), Token::Expr(Expr::NUM(f32::NAN))];
+        let mut cache = FitnessCache::new(1);
+        let bytecode = Arc::new(compile(&program));
+        cache.insert(&program, -1.0, &Evaluation::default(), &bytecode);
+        assert_eq!(cache.get(&program).map(|(f, ..)| f), Some(-1.0));
+        assert_eq!(cache.hit_rate(), 1.0);
+    }
+
+    #[test]
+    fn test_disabled_cache() {
+        let program = vec![Token::Stat(Stat::OUTPUT), Token::Reg(0)];
+        let mut cache = FitnessCache::new(0);
+        let bytecode = Arc::new(compile(&program));
+        cache.insert(&program, 0.0, &Evaluation::default(), &bytecode);
+        assert!(cache.get(&program).is_none());
+        assert_eq!(cache.hit_rate(), 0.0);
+    }
+}
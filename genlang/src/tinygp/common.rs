@@ -1,7 +1,10 @@
 use rand_derive::Rand;
 use serde_derive::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use strum_macros::EnumCount;
 
-#[derive(Debug, Clone, Copy, PartialEq, Rand, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Rand, EnumCount, Serialize, Deserialize)]
 pub enum Expr {
     ADD,
     SUB,
@@ -9,6 +12,17 @@ pub enum Expr {
     DIV,
     SIN,
     COS,
+    EXP,
+    LOG,
+    SQRT,
+    POW,
+    ABS,
+    NEG,
+    MIN,
+    MAX,
+    MOD,
+    FLOOR,
+    TANH,
     EQ,
     LT,
     GT,
@@ -18,7 +32,28 @@ pub enum Expr {
     NUM(f32),
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Rand, Serialize, Deserialize)]
+// constants are hashed by their canonical bits
+impl Hash for Expr {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        if let Expr::NUM(val) = self {
+            canonical_bits(*val).hash(state);
+        }
+    }
+}
+
+// bits of the constant with -0.0 turned into 0.0, since they compare equal, and every NaN into one
+fn canonical_bits(val: f32) -> u32 {
+    if val == 0.0 {
+        0.0f32.to_bits()
+    } else if val.is_nan() {
+        f32::NAN.to_bits()
+    } else {
+        val.to_bits()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Hash, Rand, Serialize, Deserialize)]
 pub enum Stat {
     INPUT,
     OUTPUT,
@@ -27,7 +62,7 @@ pub enum Stat {
     WHILE,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Hash, Serialize, Deserialize)]
 pub enum Token {
     Expr(Expr),
     Stat(Stat),
@@ -49,6 +84,17 @@ impl Expr {
             Expr::DIV => 2,
             Expr::SIN => 1,
             Expr::COS => 1,
+            Expr::EXP => 1,
+            Expr::LOG => 1,
+            Expr::SQRT => 1,
+            Expr::POW => 2,
+            Expr::ABS => 1,
+            Expr::NEG => 1,
+            Expr::MIN => 2,
+            Expr::MAX => 2,
+            Expr::MOD => 2,
+            Expr::FLOOR => 1,
+            Expr::TANH => 1,
             Expr::NUM(_) => 0,
             Expr::EQ => 2,
             Expr::LT => 2,
@@ -60,46 +106,181 @@ impl Expr {
     }
 }
 
+// get_node_end returns position right after the node starting at index
+// for ELSE it is the position after the END closing its IF
 pub fn get_node_end(program: &Program, index: usize) -> usize {
     match program[index] {
         // no arguments
-        Token::Reg(_) | Token::Expr(Expr::NUM(_)) => index + 1,
+        Token::Reg(_) | Token::Expr(Expr::NUM(_)) | Token::END => index + 1,
         // 1 argument
-        Token::Stat(Stat::INPUT)
-        | Token::Stat(Stat::OUTPUT)
-        | Token::Expr(Expr::SIN)
-        | Token::Expr(Expr::COS) => get_node_end(program, index + 1),
+        Token::Stat(Stat::INPUT) | Token::Stat(Stat::OUTPUT) => get_node_end(program, index + 1),
+        // expressions consume as many nodes as they take arguments
+        Token::Expr(expr) => {
+            let mut end = index + 1;
+            for _ in 0..expr.argnum() {
+                end = get_node_end(program, end);
+            }
+            end
+        }
         // 2 arguments
-        Token::Stat(Stat::LOAD)
-        | Token::Expr(Expr::ADD)
-        | Token::Expr(Expr::SUB)
-        | Token::Expr(Expr::MUL)
-        | Token::Expr(Expr::DIV) => {
+        Token::Stat(Stat::LOAD) => {
             let arg1end = get_node_end(program, index + 1);
             get_node_end(program, arg1end)
         }
-        // "parentheses counting"
-        // TODO add WHILE
-        Token::Stat(Stat::IF) | Token::ELSE => {
-            let mut level = 1;
-            let mut i = index;
-            while i < program.len() && level > 0 {
-                match program[i] {
-                    // TODO add WHILE
-                    Token::Stat(Stat::IF) => level += 1,
-                    Token::END => level -= 1,
-                    _ => (),
+        Token::Stat(Stat::IF) => {
+            let condition_end = get_node_end(program, index + 1);
+            let mut block_end = get_block_end(program, condition_end);
+            if matches!(program.get(block_end), Some(Token::ELSE)) {
+                block_end = get_block_end(program, block_end + 1);
+            }
+            (block_end + 1).min(program.len())
+        }
+        Token::Stat(Stat::WHILE) => {
+            let condition_end = get_node_end(program, index + 1);
+            let block_end = get_block_end(program, condition_end);
+            (block_end + 1).min(program.len())
+        }
+        Token::ELSE => {
+            let block_end = get_block_end(program, index + 1);
+            (block_end + 1).min(program.len())
+        }
+    }
+}
+
+// get_block_end returns position of the ELSE or END closing the block starting at index
+pub fn get_block_end(program: &Program, index: usize) -> usize {
+    let mut pos = index;
+    while pos < program.len() && !matches!(program[pos], Token::ELSE | Token::END) {
+        pos = get_node_end(program, pos);
+    }
+    pos
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NodeKind {
+    Stat,
+    Expr,
+    // register written to by INPUT or LOAD
+    Destination,
+    Else,
+    End,
+}
+
+pub fn node_kind(program: &Program, index: usize) -> NodeKind {
+    match program[index] {
+        Token::Stat(_) => NodeKind::Stat,
+        Token::Expr(_) => NodeKind::Expr,
+        Token::Reg(_) if index == 0 => NodeKind::Expr,
+        Token::Reg(_) => match program[index - 1] {
+            Token::Stat(Stat::INPUT | Stat::LOAD) => NodeKind::Destination,
+            _ => NodeKind::Expr,
+        },
+        Token::ELSE => NodeKind::Else,
+        Token::END => NodeKind::End,
+    }
+}
+
+// positions where blocks start: the program itself, IF and WHILE bodies and ELSE branches
+pub fn block_starts(program: &Program) -> Vec<usize> {
+    let mut starts = vec![0];
+    for (i, token) in program.iter().enumerate() {
+        match token {
+            Token::Stat(Stat::IF | Stat::WHILE) => starts.push(get_node_end(program, i + 1)),
+            Token::ELSE => starts.push(i + 1),
+            _ => (),
+        }
+    }
+    starts
+}
+
+// whole syntactic units, only these can be replaced without breaking the program
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnitKind {
+    Stat,
+    Expr,
+    Block,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Unit {
+    pub kind: UnitKind,
+    pub start: usize,
+    pub end: usize,
+}
+
+pub fn units(program: &Program) -> Vec<Unit> {
+    let mut units: Vec<Unit> = (0..program.len())
+        .filter_map(|start| {
+            let kind = match node_kind(program, start) {
+                NodeKind::Stat => UnitKind::Stat,
+                NodeKind::Expr => UnitKind::Expr,
+                _ => return None,
+            };
+            let end = get_node_end(program, start);
+            Some(Unit { kind, start, end })
+        })
+        .collect();
+    units.extend(block_starts(program).into_iter().map(|start| Unit {
+        kind: UnitKind::Block,
+        start,
+        end: get_block_end(program, start),
+    }));
+    units
+}
+
+pub fn structural_hash(program: &[Token]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    program.hash(&mut hasher);
+    hasher.finish()
+}
+
+// equality that agrees with structural_hash, unlike ==, a NaN constant equals itself
+pub fn same_structure(a: &[Token], b: &[Token]) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b).all(|pair| match pair {
+            (Token::Expr(Expr::NUM(x)), Token::Expr(Expr::NUM(y))) => {
+                canonical_bits(*x) == canonical_bits(*y)
+            }
+            (x, y) => x == y,
+        })
+}
+
+// depth taken up in front of the token right after the tokens, by the blocks and operators still
+// open, and the deepest any of the tokens reaches, a statement or a whole expression being 1 deep
+fn depth_profile(tokens: &[Token]) -> (usize, usize) {
+    let mut nesting: usize = 0;
+    // arguments still missing from each open operator
+    let mut open: Vec<usize> = Vec::new();
+    let mut reached = 0;
+    for token in tokens {
+        reached = reached.max(nesting + open.len() + 1);
+        match token {
+            Token::Stat(Stat::IF | Stat::WHILE) => nesting += 1,
+            Token::END => nesting = nesting.saturating_sub(1),
+            Token::Expr(expr) if expr.argnum() > 0 => open.push(expr.argnum()),
+            Token::Expr(_) | Token::Reg(_) => {
+                while let Some(missing) = open.last_mut() {
+                    *missing -= 1;
+                    if *missing > 0 {
+                        break;
+                    }
+                    open.pop();
                 }
-                i += 1;
             }
-            i
+            Token::Stat(_) | Token::ELSE => (),
         }
-        _ => todo!(),
     }
+    (nesting + open.len(), reached)
+}
+
+// depth the tokens need, growing with a depth limit keeps every program within it
+pub fn required_depth(tokens: &[Token]) -> usize {
+    depth_profile(tokens).1
 }
 
-pub fn variant_eq(a: &Token, b: &Token) -> bool {
-    std::mem::discriminant(a) == std::mem::discriminant(b)
+// depth already used up in front of the token at index, what is left is all a unit there may need
+pub fn depth_used(program: &Program, index: usize) -> usize {
+    depth_profile(&program[..index]).0
 }
 
 #[cfg(test)]
@@ -175,6 +356,209 @@ mod tests {
         assert_eq!(get_node_end(&program, 0), 16);
     }
 
+    #[test]
+    fn test_expression_end_if_followed_by_stat() {
+        #[rustfmt::skip]
+        let program = vec![
+            Token::Stat(Stat::IF),
+                Token::Expr(Expr::NUM(12.0)),
+                Token::Stat(Stat::OUTPUT), Token::Reg(0),
+            Token::ELSE,
+                Token::Stat(Stat::OUTPUT), Token::Reg(0),
+            Token::END,
+            Token::Stat(Stat::OUTPUT), Token::Reg(0),
+        ];
+        assert_eq!(get_node_end(&program, 0), 8);
+        assert_eq!(get_node_end(&program, 4), 8);
+        assert_eq!(get_node_end(&program, 7), 8);
+        assert_eq!(get_node_end(&program, 8), 10);
+    }
+
+    #[test]
+    fn test_expression_end_while() {
+        #[rustfmt::skip]
+        let program = vec![
+            Token::Stat(Stat::WHILE),
+                Token::Expr(Expr::LT), Token::Reg(0), Token::Expr(Expr::NUM(3.0)),
+                Token::Stat(Stat::IF),
+                    Token::Expr(Expr::NOT), Token::Reg(1),
+                    Token::Stat(Stat::WHILE), Token::Reg(1),
+                    Token::END,
+                Token::ELSE,
+                    Token::Stat(Stat::OUTPUT), Token::Reg(0),
+                Token::END,
+                Token::Stat(Stat::LOAD), Token::Reg(0),
+                    Token::Expr(Expr::ADD), Token::Reg(0), Token::Expr(Expr::NUM(1.0)),
+            Token::END,
+            Token::Stat(Stat::OUTPUT), Token::Reg(0),
+        ];
+        assert_eq!(get_node_end(&program, 0), 20);
+        assert_eq!(get_node_end(&program, 1), 4);
+        assert_eq!(get_node_end(&program, 4), 14);
+        assert_eq!(get_node_end(&program, 5), 7);
+        assert_eq!(get_node_end(&program, 7), 10);
+        assert_eq!(get_node_end(&program, 10), 14);
+        assert_eq!(get_node_end(&program, 14), 19);
+    }
+
+    #[test]
+    fn test_expression_end_logic() {
+        let program = vec![
+            Token::Expr(Expr::OR),
+            Token::Expr(Expr::EQ),
+            Token::Reg(0),
+            Token::Reg(1),
+            Token::Expr(Expr::AND),
+            Token::Expr(Expr::GT),
+            Token::Reg(0),
+            Token::Reg(1),
+            Token::Expr(Expr::NOT),
+            Token::Reg(0),
+        ];
+        assert_eq!(get_node_end(&program, 0), 10);
+        assert_eq!(get_node_end(&program, 1), 4);
+        assert_eq!(get_node_end(&program, 4), 10);
+        assert_eq!(get_node_end(&program, 5), 8);
+        assert_eq!(get_node_end(&program, 8), 10);
+    }
+
+    #[test]
+    fn test_expression_end_math() {
+        let program = vec![
+            Token::Expr(Expr::POW),
+            Token::Expr(Expr::LOG),
+            Token::Reg(0),
+            Token::Expr(Expr::MOD),
+            Token::Expr(Expr::TANH),
+            Token::Reg(1),
+            Token::Expr(Expr::MAX),
+            Token::Reg(0),
+            Token::Expr(Expr::NUM(2.0)),
+        ];
+        assert_eq!(get_node_end(&program, 0), 9);
+        assert_eq!(get_node_end(&program, 1), 3);
+        assert_eq!(get_node_end(&program, 3), 9);
+        assert_eq!(get_node_end(&program, 4), 6);
+        assert_eq!(get_node_end(&program, 6), 9);
+    }
+
+    #[test]
+    fn test_node_kinds_and_blocks() {
+        #[rustfmt::skip]
+        let program = vec![
+            Token::Stat(Stat::INPUT), Token::Reg(0),
+            Token::Stat(Stat::IF), Token::Reg(0),
+                Token::Stat(Stat::LOAD), Token::Reg(1), Token::Reg(0),
+            Token::ELSE,
+                Token::Stat(Stat::OUTPUT), Token::Reg(1),
+            Token::END,
+        ];
+        let kinds: Vec<NodeKind> = (0..program.len()).map(|i| node_kind(&program, i)).collect();
+        use NodeKind::{Destination, Else, End};
+        #[rustfmt::skip]
+        assert_eq!(
+            kinds,
+            vec![
+                NodeKind::Stat, Destination,
+                NodeKind::Stat, NodeKind::Expr,
+                    NodeKind::Stat, Destination, NodeKind::Expr,
+                Else,
+                    NodeKind::Stat, NodeKind::Expr,
+                End,
+            ]
+        );
+        assert_eq!(block_starts(&program), vec![0, 4, 8]);
+        assert_eq!(depth_used(&program, 2), 0);
+        assert_eq!(depth_used(&program, 4), 1);
+        assert_eq!(depth_used(&program, 10), 1);
+
+        let blocks: Vec<(usize, usize)> = units(&program)
+            .into_iter()
+            .filter(|u| u.kind == UnitKind::Block)
+            .map(|u| (u.start, u.end))
+            .collect();
+        assert_eq!(blocks, vec![(0, 11), (4, 7), (8, 10)]);
+        assert_eq!(units(&program).len(), 5 + 2 + 3);
+    }
+
+    #[test]
+    fn test_structural_hash() {
+        let output = |token| vec![Token::Stat(Stat::OUTPUT), token];
+        let num = |val| Token::Expr(Expr::NUM(val));
+        assert_eq!(
+            structural_hash(&output(num(1.5))),
+            structural_hash(&output(num(1.5)))
+        );
+        assert_ne!(
+            structural_hash(&output(num(1.5))),
+            structural_hash(&output(num(2.5)))
+        );
+        assert_ne!(
+            structural_hash(&output(Token::Reg(0))),
+            structural_hash(&output(Token::Reg(1)))
+        );
+        assert_ne!(
+            structural_hash(&output(Token::Reg(0))),
+            structural_hash(&output(num(0.0)))
+        );
+        assert_eq!(
+            structural_hash(&output(num(f32::NAN))),
+            structural_hash(&output(num(f32::NAN)))
+        );
+        // equal programs hash alike
+        assert_eq!(num(0.0), num(-0.0));
+        assert_eq!(
+            structural_hash(&output(num(0.0))),
+            structural_hash(&output(num(-0.0)))
+        );
+        assert_eq!(
+            structural_hash(&output(num(f32::NAN))),
+            structural_hash(&output(num(-f32::NAN)))
+        );
+        assert!(same_structure(
+            &output(num(f32::NAN)),
+            &output(num(-f32::NAN))
+        ));
+        assert!(same_structure(&output(num(0.0)), &output(num(-0.0))));
+        assert!(!same_structure(&output(num(1.5)), &output(num(2.5))));
+        assert!(!same_structure(&output(num(0.0)), &output(Token::Reg(0))));
+    }
+
+    #[test]
+    fn test_depth() {
+        let num = Token::Expr(Expr::NUM(1.0));
+        // OUTPUT (ADD r0 (NEG 1.0))
+        let output = vec![
+            Token::Stat(Stat::OUTPUT),
+            Token::Expr(Expr::ADD),
+            Token::Reg(0),
+            Token::Expr(Expr::NEG),
+            num,
+        ];
+        assert_eq!(required_depth(&output), 3);
+        assert_eq!(depth_used(&output, 1), 0);
+        assert_eq!(depth_used(&output, 3), 1);
+        assert_eq!(depth_used(&output, 4), 2);
+        // WHILE 1.0 (INPUT r0) END OUTPUT 1.0
+        let program = vec![
+            Token::Stat(Stat::WHILE),
+            num,
+            Token::Stat(Stat::INPUT),
+            Token::Reg(0),
+            Token::END,
+            Token::Stat(Stat::OUTPUT),
+            num,
+        ];
+        assert_eq!(required_depth(&program), 2);
+        assert_eq!(depth_used(&program, 2), 1);
+        assert_eq!(depth_used(&program, 5), 0);
+        // the same expression needs more depth inside the loop
+        let mut nested = program[..2].to_vec();
+        nested.extend_from_slice(&output);
+        nested.push(Token::END);
+        assert_eq!(required_depth(&nested), 4);
+    }
+
     #[test]
     fn test_serialize() {
         let e = Expr::ADD;
@@ -0,0 +1,186 @@
+// the dialect of the old string interpreter, handy for writing programs by hand:
+//
+// < R0 = x, output >
+// IF GT R0 0
+//   THEN
+// ELSE
+//   LOAD R0 MUL R0 -1
+// FI
+//
+// RETURN R0
+//
+// it is the text syntax with `< >` comments, an optional THEN, FI closing blocks
+// and RETURN standing for OUTPUT. Nothing can stop a program early, so RETURN
+// is only allowed as the last statement. Registers start out holding the inputs,
+// so programs of this dialect don't need to read them
+
+use std::ops::Range;
+
+use super::common::*;
+use super::syntax::{parse_words, SyntaxError};
+
+pub fn compile(text: &str, inputs: usize) -> Result<Program, SyntaxError> {
+    let words: Vec<(Range<usize>, &str)> = lex(text)?
+        .into_iter()
+        .filter(|(_, word)| !word.eq_ignore_ascii_case("THEN"))
+        .collect();
+    let last_statement = words.iter().rposition(|(_, word)| is_statement(word));
+    let returns: Vec<(usize, Range<usize>)> = words
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, word))| word.eq_ignore_ascii_case("RETURN"))
+        .map(|(i, (span, _))| (i, span.clone()))
+        .collect();
+    let words = words
+        .into_iter()
+        .map(|(span, word)| {
+            let word = match word.to_uppercase().as_str() {
+                "FI" => "END",
+                "RETURN" => "OUTPUT",
+                _ => word,
+            };
+            (span, word)
+        })
+        .collect();
+    let mut program: Program = (0..inputs)
+        .flat_map(|num| [Token::Stat(Stat::INPUT), Token::Reg(num)])
+        .collect();
+    program.extend(parse_words(text, words)?);
+
+    // statements are parsed in the order of their words, so a RETURN written last became the
+    // last statement, which also has to be outside of every block
+    let last = program
+        .iter()
+        .rposition(|token| matches!(token, Token::Stat(_)));
+    let ends_program = last.is_some_and(|pos| {
+        depth_used(&program, pos) == 0 && get_node_end(&program, pos) == program.len()
+    });
+    if let Some((_, span)) = returns
+        .into_iter()
+        .find(|&(i, _)| Some(i) != last_statement || !ends_program)
+    {
+        return Err(SyntaxError::new(
+            text,
+            span,
+            "RETURN can only be the last statement of the program".into(),
+        ));
+    }
+    Ok(program)
+}
+
+fn is_statement(word: &str) -> bool {
+    ["INPUT", "OUTPUT", "LOAD", "IF", "WHILE", "RETURN"]
+        .iter()
+        .any(|stat| word.eq_ignore_ascii_case(stat))
+}
+
+// words of the text with their byte ranges, comments may span lines
+fn lex(text: &str) -> Result<Vec<(Range<usize>, &str)>, SyntaxError> {
+    let mut words = Vec::new();
+    let mut word_start = None;
+    let mut comment_start = None;
+    for (i, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        if let Some(start) = comment_start {
+            if c == '>' {
+                comment_start = None;
+            } else if i == text.len() {
+                return Err(SyntaxError::new(
+                    text,
+                    start..start + 1,
+                    "Comment is never closed with '>'".into(),
+                ));
+            }
+            continue;
+        }
+        if c.is_whitespace() || c == '<' || c == '>' {
+            if let Some(start) = word_start.take() {
+                words.push((start..i, &text[start..i]));
+            }
+        } else if word_start.is_none() {
+            word_start = Some(i);
+        }
+        match c {
+            '<' => comment_start = Some(i),
+            '>' => {
+                return Err(SyntaxError::new(
+                    text,
+                    i..i + 1,
+                    "Unexpected '>' outside of a comment".into(),
+                ))
+            }
+            _ => (),
+        }
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::execution::{reference::execute, Runtime};
+    use super::super::syntax::pprint;
+    use super::*;
+
+    const ABS: &str = "< R0 = x, output >
+IF GT R0 0
+  THEN
+ELSE
+  LOAD R0 MUL R0 -1
+FI
+
+RETURN R0";
+
+    #[test]
+    fn test_compile_abs() {
+        let program = compile(ABS, 1).unwrap();
+        assert_eq!(
+            pprint(&program),
+            "INPUT R0\nIF GT R0 0\nELSE\n  LOAD R0 MUL R0 -1\nEND\nOUTPUT R0"
+        );
+        for (x, abs) in [(2.0, 2.0), (-2.0, 2.0), (0.0, 0.0)] {
+            assert_eq!(
+                execute(&program, Runtime::new(2, vec![x])).output,
+                vec![abs]
+            );
+        }
+    }
+
+    #[test]
+    fn test_comments() {
+        let text = "<sum of
+            both inputs>IF R0 THEN LOAD R1 R0 FI
+            RETURN ADD R0<first>R1 < second >";
+        assert_eq!(
+            pprint(&compile(text, 2).unwrap()),
+            "INPUT R0\nINPUT R1\nIF R0\n  LOAD R1 R0\nEND\nOUTPUT ADD R0 R1"
+        );
+
+        let e = compile("RETURN R0\n  < unclosed", 1).unwrap_err();
+        assert_eq!((e.line, e.column), (2, 3));
+        assert_eq!(e.reason, "Comment is never closed with '>'");
+        let e = compile("RETURN R0 >", 1).unwrap_err();
+        assert_eq!(e.reason, "Unexpected '>' outside of a comment");
+        let e = compile("< ok > RETURN", 1).unwrap_err();
+        assert_eq!(
+            e.reason,
+            "Expected an expression, got the end of the program"
+        );
+    }
+
+    #[test]
+    fn test_return_ends_the_program() {
+        let reason = "RETURN can only be the last statement of the program";
+        // the statements after an early RETURN would still run
+        let e = compile("IF R0 RETURN 1 FI\nRETURN 2", 1).unwrap_err();
+        assert_eq!((e.line, e.column, e.reason.as_str()), (1, 7, reason));
+        let e = compile("IF R0 RETURN 1 FI", 1).unwrap_err();
+        assert_eq!((e.line, e.column, e.reason.as_str()), (1, 7, reason));
+        let e = compile("RETURN R0 OUTPUT 1", 1).unwrap_err();
+        assert_eq!((e.line, e.column, e.reason.as_str()), (1, 1, reason));
+        // OUTPUT doesn't end the program and may appear anywhere
+        let program = compile("IF R0 OUTPUT 1 FI\nRETURN 2", 1).unwrap();
+        assert_eq!(
+            execute(&program, Runtime::new(1, vec![1.0])).output,
+            vec![1.0, 2.0]
+        );
+    }
+}
@@ -1,95 +1,260 @@
 use crate::params::Params;
 
 use super::common::*;
+use super::growing::*;
+use super::validation::validate;
 use rand::prelude::*;
 
-pub fn crossover(father: &Program, mother: &Program, rand: &mut StdRng) -> Program {
+// swaps a whole statement, expression or block of the father for one of the same kind from the mother
+pub fn crossover(
+    father: &Program,
+    mother: &Program,
+    params: &Params,
+    rand: &mut StdRng,
+) -> Program {
     log::trace!("crossover {father:?} x {mother:?}");
 
-    let father_start = rand.gen_range(0, father.len());
-    let father_kind = father[father_start];
-    let father_end = get_node_end(father, father_start);
+    let father_unit = *units(father).choose(rand).unwrap();
+    let father_len = father_unit.end - father_unit.start;
+    // depth the mother's unit may need to keep the offspring within depth, counting the blocks
+    // and the operators around the father's unit
+    let depth_budget = params
+        .depth
+        .saturating_sub(depth_used(father, father_unit.start));
 
-    let mother_start = match mother
-        .iter()
-        .enumerate()
-        .filter(|(_i, v)| variant_eq(&father_kind, &v))
+    let mother_unit = match units(mother)
+        .into_iter()
+        .filter(|u| {
+            u.kind == father_unit.kind
+                && father.len() - father_len + (u.end - u.start) <= MAX_LEN
+                && required_depth(&mother[u.start..u.end]) <= depth_budget
+        })
         .choose(rand)
     {
-        Some((i, _v)) => i,
+        Some(unit) => unit,
         None => {
-            log::warn!("parents non compatible, returning father");
+            log::debug!("parents non compatible, returning father");
             return father.clone();
         }
     };
-    let mother_end = get_node_end(mother, mother_start);
 
-    let mut offspring: Program = Vec::with_capacity(
-        father_start + (mother_end - mother_start) + (father.len() - father_end),
-    );
-    offspring.extend_from_slice(&father[0..father_start]);
-    offspring.extend_from_slice(&mother[mother_start..mother_end]);
-    offspring.extend_from_slice(&father[father_end..father.len()]);
+    let mut offspring: Program =
+        Vec::with_capacity(father.len() - father_len + (mother_unit.end - mother_unit.start));
+    offspring.extend_from_slice(&father[0..father_unit.start]);
+    offspring.extend_from_slice(&mother[mother_unit.start..mother_unit.end]);
+    offspring.extend_from_slice(&father[father_unit.end..father.len()]);
     log::trace!(" -> {offspring:?}");
+    debug_assert_eq!(
+        validate(&offspring, params.memsize),
+        Ok(()),
+        "{offspring:?}"
+    );
     offspring
 }
 
 pub fn mutation(parent: &Program, params: &Params, rand: &mut StdRng) -> Program {
     log::trace!("mutation");
-    let mut child = Vec::with_capacity(parent.len());
-    for i in 0..parent.len() {
-        let replacement: Token;
-        if rand.gen_bool(params.pmut_per_node as f64) {
-            match parent[i] {
-                Token::Expr(e) => {
-                    let nonterminal: Expr = rand.gen();
-                    if e.argnum() == nonterminal.argnum() {
-                        replacement = Token::Expr(nonterminal);
-                    } else {
-                        log::warn!("mutation for different argument numbers skipped");
-                        replacement = Token::Expr(e);
-                    }
-                }
-                Token::Reg(_) => {
-                    replacement = Token::Reg(rand.gen_range(0, params.memsize));
-                }
-                Token::Stat(stat) => {
-                    replacement = Token::Stat(stat);
-                },
-                _ => unimplemented!()
-            }
-        } else {
-            replacement = parent[i];
+    let mut child = if rand.gen_bool(params.subtree_mut_prob as f64) {
+        subtree_mutation(parent, params, rand)
+    } else {
+        parent.clone()
+    };
+    point_mutation(&mut child, params, rand);
+    constant_perturbation(&mut child, params, rand);
+    debug_assert_eq!(validate(&child, params.memsize), Ok(()), "{child:?}");
+    child
+}
+
+// replaces a random statement, expression or block with a freshly grown one
+pub fn subtree_mutation(parent: &Program, params: &Params, rand: &mut StdRng) -> Program {
+    let unit = *units(parent).choose(rand).unwrap();
+    let depth = params.depth.saturating_sub(depth_used(parent, unit.start));
+    let mut subtree = Vec::new();
+    match unit.kind {
+        UnitKind::Stat => {
+            grow_stat(&mut subtree, depth, Method::Grow, params, rand);
         }
-        child.push(replacement);
+        UnitKind::Expr => grow_expr(&mut subtree, depth, Method::Grow, params, rand),
+        UnitKind::Block => grow_block(&mut subtree, depth, Method::Grow, params, rand),
+    }
+    let len = parent.len() - (unit.end - unit.start) + subtree.len();
+    if len > MAX_LEN {
+        log::debug!("subtree mutation would exceed MAX_LEN, returning parent");
+        return parent.clone();
     }
+    let mut child = Vec::with_capacity(len);
+    child.extend_from_slice(&parent[..unit.start]);
+    child.extend_from_slice(&subtree);
+    child.extend_from_slice(&parent[unit.end..]);
+    log::trace!(" -> {child:?}");
     child
 }
 
-pub fn tournament(fitness: &Vec<f32>, tournament_size: usize, rand: &mut StdRng) -> usize {
-    let mut best = rand.gen_range(0, fitness.len());
-    let mut best_fitness = fitness[best];
+// changes single nodes into others taking the same arguments
+pub fn point_mutation(program: &mut Program, params: &Params, rand: &mut StdRng) {
+    for i in 0..program.len() {
+        if !rand.gen_bool(params.pmut_per_node as f64) {
+            continue;
+        }
+        let replacement = match (program[i], node_kind(program, i)) {
+            (Token::Expr(e), _) if e.argnum() > 0 => loop {
+                let nonterminal: Expr = rand.gen();
+                if nonterminal.argnum() == e.argnum() {
+                    break Token::Expr(nonterminal);
+                }
+            },
+            (_, NodeKind::Expr) => {
+                let mut terminal = Vec::with_capacity(1);
+                grow_terminal(&mut terminal, params, rand);
+                terminal[0]
+            }
+            (Token::Reg(_), NodeKind::Destination) => Token::Reg(rand.gen_range(0, params.memsize)),
+            (Token::Stat(stat), _) => Token::Stat(mutate_stat(program, i, stat)),
+            (token, _) => token,
+        };
+        program[i] = replacement;
+    }
+}
 
-    for _ in 0..tournament_size {
-        let competitor = rand.gen_range(0, fitness.len());
-        if fitness[competitor] > best_fitness {
-            best_fitness = fitness[competitor];
-            best = competitor;
+// INPUT and OUTPUT of a register, IF without ELSE and WHILE are interchangeable
+fn mutate_stat(program: &Program, index: usize, stat: Stat) -> Stat {
+    match stat {
+        Stat::INPUT => Stat::OUTPUT,
+        Stat::OUTPUT if matches!(program[index + 1], Token::Reg(_)) => Stat::INPUT,
+        Stat::IF | Stat::WHILE => {
+            let block_end = get_block_end(program, get_node_end(program, index + 1));
+            match (stat, program[block_end]) {
+                (Stat::IF, Token::END) => Stat::WHILE,
+                (Stat::WHILE, _) => Stat::IF,
+                _ => stat,
+            }
         }
+        _ => stat,
     }
-    best
 }
 
-pub fn negative_tournament(fitness: &Vec<f32>, tournament_size: usize, rand: &mut StdRng) -> usize {
-    let mut worst = rand.gen_range(0, fitness.len());
-    let mut worst_fitness = fitness[worst];
+pub fn constant_perturbation(program: &mut Program, params: &Params, rand: &mut StdRng) {
+    for token in program.iter_mut() {
+        if let Token::Expr(Expr::NUM(val)) = token {
+            if rand.gen_bool(params.pperturb_per_num as f64) {
+                *val += rand.gen_range(-params.perturb_range, params.perturb_range);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::execution::{reference::execute, Runtime};
+    use super::*;
+
+    const IF: Token = Token::Stat(Stat::IF);
+    const WHILE: Token = Token::Stat(Stat::WHILE);
+    const OUTPUT: Token = Token::Stat(Stat::OUTPUT);
+    use Token::{Reg, ELSE, END};
+
+    fn assert_well_formed(program: &Program, memsize: usize) {
+        assert_eq!(get_block_end(program, 0), program.len(), "{program:?}");
+        // execute panics on syntax errors
+        execute(program, Runtime::new(memsize, vec![1.0, 2.0]));
+    }
+
+    #[test]
+    fn test_mutations_keep_programs_well_formed() {
+        let params = Params {
+            memsize: 3,
+            depth: 4,
+            subtree_mut_prob: 1.0,
+            pmut_per_node: 0.2,
+            pperturb_per_num: 0.5,
+            ..Default::default()
+        };
+        let mut rand = StdRng::seed_from_u64(0);
+        for mut program in random_programs(0, 200, &params) {
+            for _ in 0..5 {
+                program = mutation(&program, &params, &mut rand);
+                assert_well_formed(&program, params.memsize);
+                assert!(required_depth(&program) <= params.depth, "{program:?}");
+            }
+        }
+    }
 
-    for _ in 0..tournament_size {
-        let competitor = rand.gen_range(0, fitness.len());
-        if fitness[competitor] < worst_fitness {
-            worst_fitness = fitness[competitor];
-            worst = competitor;
+    #[test]
+    fn test_crossover_keeps_programs_well_formed() {
+        let params = Params {
+            memsize: 3,
+            depth: 4,
+            ..Default::default()
+        };
+        let mut rand = StdRng::seed_from_u64(3);
+        let mut population = random_programs(3, 50, &params);
+        for _ in 0..2000 {
+            let father = rand.gen_range(0, population.len());
+            let mother = rand.gen_range(0, population.len());
+            let child = crossover(&population[father], &population[mother], &params, &mut rand);
+            assert_well_formed(&child, params.memsize);
+            assert!(required_depth(&child) <= params.depth, "{child:?}");
+            population[father] = child;
+        }
+    }
+
+    #[test]
+    fn test_crossover_swaps_same_kind() {
+        let params = Params {
+            memsize: 2,
+            depth: 3,
+            ..Default::default()
+        };
+        let mut rand = StdRng::seed_from_u64(4);
+        // only expressions of the father and the mother differ
+        let father = vec![OUTPUT, Token::Expr(Expr::NUM(1.0))];
+        let mother = vec![OUTPUT, Reg(1)];
+        for _ in 0..50 {
+            let child = crossover(&father, &mother, &params, &mut rand);
+            assert!(child == father || child == mother, "{child:?}");
+        }
+    }
+
+    #[test]
+    fn test_point_mutation_keeps_else() {
+        let params = Params {
+            memsize: 2,
+            pmut_per_node: 1.0,
+            ..Default::default()
+        };
+        let mut rand = StdRng::seed_from_u64(1);
+        #[rustfmt::skip]
+        let mut program = vec![
+            IF, Reg(0),
+                OUTPUT, Reg(1),
+            ELSE,
+            END,
+            WHILE, Reg(0),
+            END,
+        ];
+        point_mutation(&mut program, &params, &mut rand);
+        assert_eq!(program[0], IF);
+        assert_eq!(program[2], Token::Stat(Stat::INPUT));
+        assert_eq!(program[6], IF);
+        assert_well_formed(&program, params.memsize);
+    }
+
+    #[test]
+    fn test_constant_perturbation() {
+        let params = Params {
+            pperturb_per_num: 1.0,
+            perturb_range: 0.5,
+            ..Default::default()
+        };
+        let mut rand = StdRng::seed_from_u64(2);
+        let mut program = vec![OUTPUT, Token::Expr(Expr::NUM(3.0))];
+        constant_perturbation(&mut program, &params, &mut rand);
+        match program[1] {
+            Token::Expr(Expr::NUM(val)) => {
+                assert_ne!(val, 3.0);
+                assert!((val - 3.0).abs() <= 0.5);
+            }
+            _ => panic!("constant perturbation changed the node kind"),
         }
     }
-    worst
 }
@@ -1,12 +1,52 @@
 use super::common::*;
+use crate::params::{MemoryInit, OutOfRange, Params};
+
+#[cfg(test)]
+pub mod reference;
 
 #[allow(unused)]
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum EvalError {
     Finished,
     Syntax(usize, String),
     Semantic(String),
-    MaxIteration
+    MaxIteration,
+    // the whole run took more steps than its budget
+    StepLimit,
+    OutputLimit,
+}
+
+// why a run stopped, running out of input is the usual way for a program to finish
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Termination {
+    Finished,
+    MaxIteration,
+    Semantic,
+    StepLimit,
+    OutputLimit,
+}
+
+// everything a program left behind after running on one input
+#[derive(Debug, Clone)]
+pub struct ExecutionReport {
+    pub output: Vec<f32>,
+    // None when the program ran to its end, Finished when it ran out of input
+    pub error: Option<EvalError>,
+    // statements and expressions evaluated
+    pub steps: usize,
+    pub memory: Vec<f32>,
+}
+
+impl ExecutionReport {
+    pub fn termination(&self) -> Termination {
+        match self.error {
+            None | Some(EvalError::Finished) => Termination::Finished,
+            Some(EvalError::MaxIteration) => Termination::MaxIteration,
+            Some(EvalError::Semantic(_) | EvalError::Syntax(..)) => Termination::Semantic,
+            Some(EvalError::StepLimit) => Termination::StepLimit,
+            Some(EvalError::OutputLimit) => Termination::OutputLimit,
+        }
+    }
 }
 
 pub struct Runtime {
@@ -14,7 +54,11 @@ pub struct Runtime {
     input: Vec<f32>,
     output: Vec<f32>,
     input_cursor: usize,
-    max_iterations: usize,
+    pub(super) max_iterations: usize,
+    max_steps: usize,
+    max_output_len: usize,
+    out_of_range: OutOfRange,
+    steps: usize,
 }
 
 impl Runtime {
@@ -24,263 +68,138 @@ impl Runtime {
             input,
             output: Vec::new(),
             input_cursor: 0,
-            max_iterations: 100
+            max_iterations: 100,
+            max_steps: usize::MAX,
+            max_output_len: usize::MAX,
+            out_of_range: OutOfRange::Error,
+            steps: 0,
         }
     }
 
-    pub fn next_input(&mut self) -> Option<f32> {
-        if self.input_cursor < self.input.len() {
-            let val = self.input[self.input_cursor];
-            self.input_cursor += 1;
-            Some(val)
-        } else {
-            None
+    // memory and budgets of the problem
+    pub fn with_params(params: &Params, input: Vec<f32>) -> Self {
+        let mut memory = vec![0.0; params.memsize];
+        let initial = match params.memory_init {
+            MemoryInit::Zero => &[][..],
+            MemoryInit::Inputs => &input[..],
+            MemoryInit::Random => &params.constants[..],
+        };
+        for (register, &val) in memory.iter_mut().zip(initial) {
+            *register = val;
         }
-    }
-
-    pub fn set_reg(&mut self, num: usize, val: f32) -> Result<(), EvalError> {
-        if num > self.memory.len() {
-            Err(EvalError::Semantic(format!(
-                "Tried to set memory[{num}], when length is {}",
-                self.memory.len()
-            )))
-        } else {
-            self.memory[num] = val;
-            Ok(())
+        Runtime {
+            memory,
+            max_iterations: params.max_iterations,
+            max_steps: params.max_steps,
+            max_output_len: params.max_output_len,
+            out_of_range: params.out_of_range,
+            ..Runtime::new(0, input)
         }
     }
 
-    pub fn read_reg(&self, num: usize) -> Result<f32, EvalError> {
-        if num > self.memory.len() {
-            Err(EvalError::Semantic(format!(
-                "Tried to read memory[{num}], when length is {}",
-                self.memory.len()
-            )))
-        } else {
-            Ok(self.memory[num].clone())
+    // counts a statement or expression against the budget, in postfix order: an operator,
+    // OUTPUT, LOAD and IF are counted after their arguments, INPUT and WHILE before anything else
+    pub fn step(&mut self) -> Result<(), EvalError> {
+        if self.steps >= self.max_steps {
+            return Err(EvalError::StepLimit);
         }
+        self.steps += 1;
+        Ok(())
     }
-}
-
-pub fn execute(program: &Program, runtime: Runtime) -> Vec<f32> {
-    log::trace!("executing {:?}", program);
-    let mut runtime = runtime;
-    return match eval_block(program, 0, &mut runtime) {
-        Ok(pos) => {
-            log::trace!("program ended with output {:?}", runtime.output);
-            log::trace!("finished at pos {}/{}", pos, program.len() - 1);
-            runtime.output
-        }
-        Err(EvalError::Finished) => {
-            log::trace!(
-                "terminated due to input end with output {:?}",
-                runtime.output
-            );
-            runtime.output
-        }
-        Err(EvalError::MaxIteration) => {
-            log::trace!(
-                "terminated due reaching max iteration {:?}",
-                runtime.output
-            );
-            runtime.output
-        }
-        Err(EvalError::Syntax(pos, reason)) => {
-            log::error!("Invalid program: {program:?}");
-            log::error!("Invalid syntax at {pos}: {reason}");
-            panic!("Invalid syntax at {pos}: {reason}");
-        }
-        Err(EvalError::Semantic(reason)) => {
-            log::error!("Invalid program: {program:?}");
-            log::error!("Invalid program reason: {reason}");
-            vec![f32::INFINITY]
-        }
-    };
-}
 
-// eval_block returns position after the last STAT. This means the cursor will point to ELSE or END tokens
-fn eval_block(program: &Program, pos: usize, runtime: &mut Runtime) -> Result<usize, EvalError> {
-    log::trace!("eval block {pos}");
-    let mut pos = pos;
-    loop {
-        if pos >= program.len() || matches!(program[pos], Token::ELSE | Token::END) {
-            log::trace!("returning from block, returning {pos}");
-            return Ok(pos);
+    pub fn write(&mut self, val: f32) -> Result<(), EvalError> {
+        if self.output.len() >= self.max_output_len {
+            return Err(EvalError::OutputLimit);
         }
-        pos = eval_stat(program, pos, runtime)?;
+        self.output.push(val);
+        Ok(())
     }
-}
-
-fn is_truthy(x: f32) -> bool {
-    x != 0.0
-}
 
-fn handle_if_true(
-    program: &Program,
-    pos: usize,
-    runtime: &mut Runtime,
-) -> Result<usize, EvalError> {
-    let end_or_else = eval_block(program, pos, runtime)?;
-    match program[end_or_else] {
-        Token::ELSE => {
-            let else_part_end = get_node_end(program, end_or_else);
-            log::trace!("got node end {else_part_end}");
-            Ok(else_part_end)
+    pub fn report(self, error: Option<EvalError>) -> ExecutionReport {
+        ExecutionReport {
+            output: self.output,
+            error,
+            steps: self.steps,
+            memory: self.memory,
         }
-        Token::END => Ok(end_or_else + 1),
-        _ => Err(EvalError::Syntax(
-            end_or_else,
-            "Expected END or ELSE".into(),
-        )),
     }
-}
 
-fn handle_if_false(
-    program: &Program,
-    true_block_pos: usize,
-    runtime: &mut Runtime,
-    if_stat_pos: usize,
-) -> Result<usize, EvalError> {
-    let true_block_end = skip_block(program, true_block_pos);
-    match program[true_block_end] {
-        Token::ELSE => {
-            let else_block_pos = true_block_end + 1;
-            log::trace!("IF condition at {if_stat_pos} entered ELSE branch at {else_block_pos}");
-            let endpos = eval_block(program, else_block_pos, runtime)?;
-            Ok(endpos + 1)
-        }
-        Token::END => {
-            log::trace!(
-                "IF condition at {if_stat_pos} has no ELSE branch (reached END at {true_block_end}"
-            );
-            Ok(true_block_end + 1)
+    pub fn next_input(&mut self) -> Option<f32> {
+        if self.input_cursor < self.input.len() {
+            let val = self.input[self.input_cursor];
+            self.input_cursor += 1;
+            Some(val)
+        } else {
+            None
         }
-        _ => unreachable!(),
     }
-}
 
-fn skip_block(program: &Program, pos: usize) -> usize {
-    let mut level = 1;
-    let mut cursor = pos;
-    while cursor < program.len() && level > 0 {
-        cursor += 1;
-        match program[cursor] {
-            Token::Stat(Stat::IF | Stat::WHILE) => level += 1,
-            Token::END => level -= 1,
-            Token::ELSE if level == 1 => level -= 1,
-            _ => (),
+    pub fn set_reg(&mut self, num: usize, val: f32) -> Result<(), EvalError> {
+        if let Some(index) = self.register(num, "set")? {
+            self.memory[index] = val;
         }
+        Ok(())
     }
-    cursor
-}
 
-fn handle_while(program: &Program, pos: usize, runtime: &mut Runtime) -> Result<usize, EvalError> {
-    let while_pos = pos;
-    let expr_pos = pos + 1;
-    let block_pos;
-    let mut expr_val;
-    (block_pos, expr_val) = eval_expr(program, expr_pos, runtime)?;
-    let block_end_pos = skip_block(program, block_pos);
-    let mut iteration = 0;
-
-    while is_truthy(expr_val) {
-        log::trace!("WHILE at {while_pos}: iteration {iteration}");
-        eval_block(program, block_pos, runtime)?;
-        (_, expr_val) = eval_expr(program, expr_pos, runtime)?;
-        iteration += 1;
-        if iteration >= runtime.max_iterations {
-            return Err(EvalError::MaxIteration)
-        }
+    pub fn read_reg(&self, num: usize) -> Result<f32, EvalError> {
+        Ok(self
+            .register(num, "read")?
+            .map_or(0.0, |index| self.memory[index]))
     }
 
-    match program[block_end_pos] {
-        Token::END => Ok(block_end_pos + 1),
-        _ => Err(EvalError::Syntax(block_end_pos, format!("Expected END after a WHILE started at {while_pos}. Got to {block_end_pos}")))
+    // index of the register in memory, None when the access doesn't reach the memory
+    fn register(&self, num: usize, access: &str) -> Result<Option<usize>, EvalError> {
+        let len = self.memory.len();
+        if num < len {
+            return Ok(Some(num));
+        }
+        match self.out_of_range {
+            OutOfRange::Error => Err(EvalError::Semantic(format!(
+                "Tried to {access} memory[{num}], when length is {len}"
+            ))),
+            OutOfRange::Wrap if len > 0 => Ok(Some(num % len)),
+            OutOfRange::Wrap | OutOfRange::Zero => Ok(None),
+        }
     }
 }
 
-fn eval_stat(program: &Program, pos: usize, runtime: &mut Runtime) -> Result<usize, EvalError> {
-    log::trace!("eval stat {pos}");
-    match program[pos] {
-        Token::Stat(stat) => match stat {
-            Stat::OUTPUT => {
-                let (newpos, val) = eval_expr(program, pos + 1, runtime)?;
-                runtime.output.push(val);
-                Ok(newpos)
-            }
-            Stat::INPUT => {
-                let destination = match program[pos + 1] {
-                    Token::Reg(num) => num,
-                    _ => return Err(EvalError::Syntax(pos + 1, "Expected REG".into())),
-                };
-                let val = match runtime.next_input() {
-                    Some(val) => val,
-                    None => return Err(EvalError::Finished),
-                };
-                runtime.set_reg(destination, val)?;
-                Ok(pos + 2)
-            }
-            Stat::LOAD => {
-                let destination = match program[pos + 1] {
-                    Token::Reg(num) => num,
-                    _ => return Err(EvalError::Syntax(pos + 1, "Expected REG".into())),
-                };
-                let (newpos, val) = eval_expr(program, pos + 2, runtime)?;
-                runtime.set_reg(destination, val)?;
-                Ok(newpos)
-            }
-            Stat::IF => {
-                let (true_block_pos, condition_val) = eval_expr(program, pos + 1, runtime)?;
-                if is_truthy(condition_val) {
-                    log::trace!("IF condition at {pos} evaluated to TRUE");
-                    handle_if_true(program, true_block_pos, runtime)
-                } else {
-                    log::trace!("IF condition at {pos} evaluated to FALSE");
-                    handle_if_false(program, true_block_pos, runtime, pos)
-                }
-            }
-            Stat::WHILE => handle_while(program, pos, runtime),
-        },
-        _ => panic!("called eval_stat on non-stat at {pos}"),
-    }
+pub fn is_truthy(x: f32) -> bool {
+    x != 0.0
 }
 
-fn eval_expr(
-    program: &Program,
-    pos: usize,
-    runtime: &mut Runtime,
-) -> Result<(usize, f32), EvalError> {
-    let opcode = program[pos];
-
-    let one_arg = |func: fn(f32) -> f32, runtime: &mut Runtime| {
-        let (pos, arg) = eval_expr(program, pos + 1, runtime)?;
-        Ok((pos, func(arg)))
-    };
-
-    let two_arg = |func: fn(f32, f32) -> f32, runtime: &mut Runtime| {
-        let (pos, lhs) = eval_expr(program, pos + 1, runtime)?;
-        let (pos, rhs) = eval_expr(program, pos, runtime)?;
-        Ok((pos, func(lhs, rhs)))
-    };
+#[derive(Debug, Clone, Copy)]
+pub enum Operator {
+    Unary(fn(f32) -> f32),
+    Binary(fn(f32, f32) -> f32),
+}
 
-    return match opcode {
-        Token::Expr(func) => match func {
-            Expr::NUM(val) => Ok((pos + 1, val)),
-            Expr::ADD => two_arg(add, runtime),
-            Expr::SUB => two_arg(sub, runtime),
-            Expr::MUL => two_arg(mul, runtime),
-            Expr::DIV => two_arg(protected_div, runtime),
-            Expr::SIN => one_arg(f32::sin, runtime),
-            Expr::COS => one_arg(f32::cos, runtime),
-            Expr::EQ => two_arg(equal, runtime),
-            Expr::LT => two_arg(less_than, runtime),
-            Expr::GT => two_arg(greater_than, runtime),
-            Expr::OR => two_arg(or, runtime),
-            Expr::AND => two_arg(and, runtime),
-            Expr::NOT => one_arg(negation, runtime),
-        },
-        Token::Reg(num) => Ok((pos + 1, runtime.read_reg(num)?)),
-        _ => unreachable!("called eval_expr on non-expr: {opcode:?}"),
+// what an expression computes from its arguments, NUM has no arguments and no operator
+pub fn operator(expr: Expr) -> Operator {
+    return match expr {
+        Expr::NUM(_) => unreachable!("NUM has no operator"),
+        Expr::ADD => Operator::Binary(add),
+        Expr::SUB => Operator::Binary(sub),
+        Expr::MUL => Operator::Binary(mul),
+        Expr::DIV => Operator::Binary(protected_div),
+        Expr::SIN => Operator::Unary(f32::sin),
+        Expr::COS => Operator::Unary(f32::cos),
+        Expr::EXP => Operator::Unary(protected_exp),
+        Expr::LOG => Operator::Unary(protected_log),
+        Expr::SQRT => Operator::Unary(protected_sqrt),
+        Expr::POW => Operator::Binary(protected_pow),
+        Expr::ABS => Operator::Unary(f32::abs),
+        Expr::NEG => Operator::Unary(negative),
+        Expr::MIN => Operator::Binary(f32::min),
+        Expr::MAX => Operator::Binary(f32::max),
+        Expr::MOD => Operator::Binary(protected_mod),
+        Expr::FLOOR => Operator::Unary(f32::floor),
+        Expr::TANH => Operator::Unary(f32::tanh),
+        Expr::EQ => Operator::Binary(equal),
+        Expr::LT => Operator::Binary(less_than),
+        Expr::GT => Operator::Binary(greater_than),
+        Expr::OR => Operator::Binary(or),
+        Expr::AND => Operator::Binary(and),
+        Expr::NOT => Operator::Unary(negation),
     };
 
     fn add(lhs: f32, rhs: f32) -> f32 {
@@ -299,6 +218,43 @@ fn eval_expr(
             lhs / rhs
         }
     }
+    // like DIV, the protected operators give back their first argument where they are undefined
+    fn protected_exp(arg: f32) -> f32 {
+        let val = arg.exp();
+        if val.is_finite() {
+            val
+        } else {
+            arg
+        }
+    }
+    fn protected_log(arg: f32) -> f32 {
+        if arg.abs() <= 0.001 {
+            arg
+        } else {
+            arg.abs().ln()
+        }
+    }
+    fn protected_sqrt(arg: f32) -> f32 {
+        arg.abs().sqrt()
+    }
+    fn protected_pow(lhs: f32, rhs: f32) -> f32 {
+        let val = lhs.abs().powf(rhs);
+        if val.is_finite() {
+            val
+        } else {
+            lhs
+        }
+    }
+    fn protected_mod(lhs: f32, rhs: f32) -> f32 {
+        if rhs.abs() <= 0.001 {
+            lhs
+        } else {
+            lhs % rhs
+        }
+    }
+    fn negative(arg: f32) -> f32 {
+        -arg
+    }
     fn equal(lhs: f32, rhs: f32) -> f32 {
         if lhs == rhs {
             1.0
@@ -345,7 +301,9 @@ fn eval_expr(
 
 #[cfg(test)]
 mod tests {
+    use super::reference::execute;
     use super::*;
+    use crate::params::Params;
     use pretty_assertions::assert_eq;
 
     #[test]
@@ -358,93 +316,160 @@ mod tests {
     }
 
     #[test]
-    fn test_stat_input() {
-        let program: Vec<Token> = vec![Token::Stat(Stat::INPUT), Token::Reg(0)];
-        let mut runtime = Runtime::new(2, vec![2.0]);
-        assert_eq!(runtime.memory, vec![0.0, 0.0]);
-        assert_eq!(runtime.input.len(), 1);
-        let res = eval_stat(&program, 0, &mut runtime);
-        assert!(res.is_ok());
-        assert_eq!(runtime.memory, vec![2.0, 0.0]);
-        let res = eval_stat(&program, 0, &mut runtime);
-        assert!(matches!(res, Err(EvalError::Finished)));
-    }
-
-    #[test]
-    fn test_stat_input_multiple() {
-        let program: Vec<Token> = vec![Token::Stat(Stat::INPUT), Token::Reg(0)];
-        let mut runtime = Runtime::new(2, vec![2.0, 3.0]);
-        assert_eq!(runtime.memory, vec![0.0, 0.0]);
-        assert_eq!(runtime.input.len(), 2);
-
-        let res = eval_stat(&program, 0, &mut runtime);
-        assert!(res.is_ok());
-        assert_eq!(runtime.memory, vec![2.0, 0.0]);
+    fn test_run_termination_and_steps() {
+        let program: Vec<Token> = vec![
+            Token::Stat(Stat::INPUT),
+            Token::Reg(0),
+            Token::Stat(Stat::OUTPUT),
+            Token::Reg(0),
+        ];
+        let report = execute(&program, Runtime::new(2, vec![2.0]));
+        assert_eq!(report.output, vec![2.0]);
+        assert_eq!(report.error, None);
+        assert_eq!(report.termination(), Termination::Finished);
+        assert_eq!(report.steps, 3);
+        assert_eq!(report.memory, vec![2.0, 0.0]);
+
+        let report = execute(&program, Runtime::new(2, vec![]));
+        assert_eq!(report.error, Some(EvalError::Finished));
+        assert_eq!(report.termination(), Termination::Finished);
+        assert_eq!(report.steps, 1);
 
-        let res = eval_stat(&program, 0, &mut runtime);
-        assert!(res.is_ok());
-        assert_eq!(runtime.memory, vec![3.0, 0.0]);
+        let program: Vec<Token> = vec![
+            Token::Stat(Stat::WHILE),
+            Token::Expr(Expr::NUM(1.0)),
+            Token::Stat(Stat::OUTPUT),
+            Token::Expr(Expr::NUM(2.0)),
+            Token::END,
+        ];
+        let report = execute(&program, Runtime::new(1, vec![]));
+        assert_eq!(report.error, Some(EvalError::MaxIteration));
+        assert_eq!(report.termination(), Termination::MaxIteration);
+        assert_eq!(report.output.len(), 100);
 
-        let res = eval_stat(&program, 0, &mut runtime);
-        assert!(matches!(res, Err(EvalError::Finished)));
+        // the output written before the crash is kept
+        let program: Vec<Token> = vec![
+            Token::Stat(Stat::OUTPUT),
+            Token::Expr(Expr::NUM(1.0)),
+            Token::Stat(Stat::OUTPUT),
+            Token::Reg(5),
+        ];
+        let report = execute(&program, Runtime::new(2, vec![]));
+        assert!(matches!(report.error, Some(EvalError::Semantic(_))));
+        assert_eq!(report.termination(), Termination::Semantic);
+        assert_eq!(report.output, vec![1.0]);
     }
 
     #[test]
-    fn test_stat_input_second_register() {
-        let program: Vec<Token> = vec![Token::Stat(Stat::INPUT), Token::Reg(1)];
-        let mut runtime = Runtime::new(2, vec![4.0]);
-        assert_eq!(runtime.memory, vec![0.0, 0.0]);
-        let res = eval_stat(&program, 0, &mut runtime);
-        assert!(res.is_ok());
-        assert_eq!(runtime.memory, vec![0.0, 4.0]);
+    fn test_registers_out_of_range() {
+        let program: Vec<Token> = vec![
+            Token::Stat(Stat::LOAD),
+            Token::Reg(3),
+            Token::Expr(Expr::NUM(5.0)),
+            Token::Stat(Stat::OUTPUT),
+            Token::Reg(2),
+            Token::Stat(Stat::OUTPUT),
+            Token::Reg(4),
+        ];
+        let mut params = Params {
+            memsize: 2,
+            ..Default::default()
+        };
+        let report = execute(&program, Runtime::with_params(&params, vec![]));
+        assert_eq!(
+            report.error,
+            Some(EvalError::Semantic(
+                "Tried to set memory[3], when length is 2".into()
+            ))
+        );
+        // the register right past the memory is an error as well
+        let report = execute(
+            &program[3..].to_vec(),
+            Runtime::with_params(&params, vec![]),
+        );
+        assert_eq!(
+            report.error,
+            Some(EvalError::Semantic(
+                "Tried to read memory[2], when length is 2".into()
+            ))
+        );
+
+        params.out_of_range = OutOfRange::Wrap;
+        let report = execute(&program, Runtime::with_params(&params, vec![]));
+        assert_eq!(report.output, vec![0.0, 0.0]);
+        assert_eq!(report.memory, vec![0.0, 5.0]);
+
+        params.out_of_range = OutOfRange::Zero;
+        params.memory_init = MemoryInit::Random;
+        params.constants = vec![7.0, 8.0];
+        let report = execute(&program, Runtime::with_params(&params, vec![]));
+        assert_eq!(report.output, vec![0.0, 0.0]);
+        assert_eq!(report.memory, vec![7.0, 8.0]);
     }
 
     #[test]
-    fn test_stat_output() {
-        let program: Vec<Token> = vec![Token::Stat(Stat::OUTPUT), Token::Reg(0)];
-        let mut runtime = Runtime {
-            memory: vec![2.0, 0.0],
-            input: vec![],
-            output: vec![],
-            input_cursor: 0,
-            max_iterations: 100
+    fn test_memory_init() {
+        let program: Vec<Token> = vec![Token::Stat(Stat::OUTPUT), Token::Reg(1)];
+        let mut params = Params {
+            memsize: 3,
+            ..Default::default()
+        };
+        let run = |params: &Params, input: Vec<f32>| {
+            let report = execute(&program, Runtime::with_params(params, input));
+            (report.output, report.memory)
         };
-        let res = eval_stat(&program, 0, &mut runtime);
-        assert!(res.is_ok());
-        assert_eq!(runtime.memory, vec![2.0, 0.0]);
-        assert_eq!(runtime.output, vec![2.0]);
+        assert_eq!(run(&params, vec![4.0, 5.0]), (vec![0.0], vec![0.0; 3]));
+        params.memory_init = MemoryInit::Inputs;
+        assert_eq!(
+            run(&params, vec![4.0, 5.0]),
+            (vec![5.0], vec![4.0, 5.0, 0.0])
+        );
+        assert_eq!(
+            run(&params, vec![4.0, 5.0, 6.0, 7.0]),
+            (vec![5.0], vec![4.0, 5.0, 6.0])
+        );
+        // missing constants leave the registers at 0
+        params.memory_init = MemoryInit::Random;
+        params.constants = vec![-1.0, 0.5];
+        assert_eq!(run(&params, vec![4.0]), (vec![0.5], vec![-1.0, 0.5, 0.0]));
     }
 
     #[test]
-    fn test_expression() {
+    fn test_budgets() {
+        // 100 iterations of 100 iterations are within the limits of each loop
+        #[rustfmt::skip]
         let program: Vec<Token> = vec![
-            Token::Expr(Expr::ADD),
-            Token::Reg(0),
-            Token::Expr(Expr::DIV),
-            Token::Reg(1),
-            Token::Reg(1),
+            Token::Stat(Stat::WHILE), Token::Expr(Expr::NUM(1.0)),
+                Token::Stat(Stat::WHILE), Token::Expr(Expr::NUM(1.0)),
+                    Token::Stat(Stat::LOAD), Token::Reg(0), Token::Expr(Expr::ADD), Token::Reg(0), Token::Expr(Expr::NUM(1.0)),
+                Token::END,
+            Token::END,
         ];
-        let data = vec![1.0, -2.0];
-        let mut runtime =  Runtime::new(3, vec![]);
-        runtime.memory = data;
-        let (pos, val) = eval_expr(&program, 0, &mut runtime).unwrap();
-        assert_eq!(5, pos);
-        assert_eq!(2.0, val);
-    }
+        let params = Params {
+            memsize: 1,
+            max_iterations: 1000,
+            max_steps: 1000,
+            ..Default::default()
+        };
+        let report = execute(&program, Runtime::with_params(&params, vec![]));
+        assert_eq!(report.error, Some(EvalError::StepLimit));
+        assert_eq!(report.termination(), Termination::StepLimit);
+        assert_eq!(report.steps, 1000);
+        assert!(report.memory[0] > 100.0);
 
-    #[test]
-    fn test_exec_identity() {
         let program: Vec<Token> = vec![
-            Token::Stat(Stat::INPUT),
-            Token::Reg(0),
+            Token::Stat(Stat::WHILE),
+            Token::Expr(Expr::NUM(1.0)),
             Token::Stat(Stat::OUTPUT),
-            Token::Reg(0),
+            Token::Expr(Expr::NUM(2.0)),
+            Token::END,
         ];
-        let mut runtime = Runtime::new(2, vec![2.0]);
-        let res = eval_stat(&program, 0, &mut runtime);
-        assert!(res.is_ok());
-        let res = eval_stat(&program, 2, &mut runtime);
-        assert!(res.is_ok());
-        assert_eq!(runtime.output, vec![2.0]);
+        let params = Params {
+            max_output_len: 7,
+            ..Default::default()
+        };
+        let report = execute(&program, Runtime::with_params(&params, vec![]));
+        assert_eq!(report.error, Some(EvalError::OutputLimit));
+        assert_eq!(report.output, vec![2.0; 7]);
     }
 }
@@ -0,0 +1,398 @@
+// the tree-walking interpreter programs were run with before they were compiled to bytecode,
+// kept as the reference the bytecode is checked against
+
+use super::super::bytecode::compile;
+use super::super::common::*;
+use super::*;
+
+// runs the program the way fitness evaluation does
+pub fn execute(program: &Program, runtime: Runtime) -> ExecutionReport {
+    compile(program).run(runtime)
+}
+
+pub fn interpret(program: &Program, runtime: Runtime) -> ExecutionReport {
+    log::trace!("executing {:?}", program);
+    let mut runtime = runtime;
+    let result = eval_block(program, 0, &mut runtime);
+    match result {
+        Ok(pos) => {
+            log::trace!("program ended with output {:?}", runtime.output);
+            log::trace!("finished at pos {}/{}", pos, program.len() - 1);
+            runtime.report(None)
+        }
+        Err(EvalError::Finished) => {
+            log::trace!(
+                "terminated due to input end with output {:?}",
+                runtime.output
+            );
+            runtime.report(Some(EvalError::Finished))
+        }
+        Err(EvalError::MaxIteration) => {
+            log::trace!("terminated due reaching max iteration {:?}", runtime.output);
+            runtime.report(Some(EvalError::MaxIteration))
+        }
+        Err(error @ (EvalError::StepLimit | EvalError::OutputLimit)) => {
+            log::trace!("ran out of budget with output {:?}", runtime.output);
+            runtime.report(Some(error))
+        }
+        Err(EvalError::Syntax(pos, reason)) => {
+            log::error!("Invalid program: {program:?}");
+            log::error!("Invalid syntax at {pos}: {reason}");
+            panic!("Invalid syntax at {pos}: {reason}");
+        }
+        Err(EvalError::Semantic(reason)) => {
+            log::error!("Invalid program: {program:?}");
+            log::error!("Invalid program reason: {reason}");
+            runtime.report(Some(EvalError::Semantic(reason)))
+        }
+    }
+}
+
+// eval_block returns position after the last STAT. This means the cursor will point to ELSE or END tokens
+fn eval_block(program: &Program, pos: usize, runtime: &mut Runtime) -> Result<usize, EvalError> {
+    log::trace!("eval block {pos}");
+    let mut pos = pos;
+    loop {
+        if pos >= program.len() || matches!(program[pos], Token::ELSE | Token::END) {
+            log::trace!("returning from block, returning {pos}");
+            return Ok(pos);
+        }
+        pos = eval_stat(program, pos, runtime)?;
+    }
+}
+
+fn handle_if_true(
+    program: &Program,
+    pos: usize,
+    runtime: &mut Runtime,
+) -> Result<usize, EvalError> {
+    let end_or_else = eval_block(program, pos, runtime)?;
+    match program[end_or_else] {
+        Token::ELSE => {
+            let else_part_end = skip_block(program, end_or_else + 1);
+            log::trace!("got node end {else_part_end}");
+            Ok(else_part_end + 1)
+        }
+        Token::END => Ok(end_or_else + 1),
+        _ => Err(EvalError::Syntax(
+            end_or_else,
+            "Expected END or ELSE".into(),
+        )),
+    }
+}
+
+fn handle_if_false(
+    program: &Program,
+    true_block_pos: usize,
+    runtime: &mut Runtime,
+    if_stat_pos: usize,
+) -> Result<usize, EvalError> {
+    let true_block_end = skip_block(program, true_block_pos);
+    match program[true_block_end] {
+        Token::ELSE => {
+            let else_block_pos = true_block_end + 1;
+            log::trace!("IF condition at {if_stat_pos} entered ELSE branch at {else_block_pos}");
+            let endpos = eval_block(program, else_block_pos, runtime)?;
+            Ok(endpos + 1)
+        }
+        Token::END => {
+            log::trace!(
+                "IF condition at {if_stat_pos} has no ELSE branch (reached END at {true_block_end}"
+            );
+            Ok(true_block_end + 1)
+        }
+        _ => unreachable!(),
+    }
+}
+
+// skip_block returns position of the ELSE or END closing the block starting at pos
+fn skip_block(program: &Program, pos: usize) -> usize {
+    let mut level = 0;
+    let mut cursor = pos;
+    while cursor < program.len() {
+        match program[cursor] {
+            Token::Stat(Stat::IF | Stat::WHILE) => level += 1,
+            Token::ELSE | Token::END if level == 0 => break,
+            Token::END => level -= 1,
+            _ => (),
+        }
+        cursor += 1;
+    }
+    cursor
+}
+
+fn handle_while(program: &Program, pos: usize, runtime: &mut Runtime) -> Result<usize, EvalError> {
+    runtime.step()?;
+    let while_pos = pos;
+    let expr_pos = pos + 1;
+    let block_pos;
+    let mut expr_val;
+    (block_pos, expr_val) = eval_expr(program, expr_pos, runtime)?;
+    let block_end_pos = skip_block(program, block_pos);
+    let mut iteration = 0;
+
+    while is_truthy(expr_val) {
+        log::trace!("WHILE at {while_pos}: iteration {iteration}");
+        eval_block(program, block_pos, runtime)?;
+        (_, expr_val) = eval_expr(program, expr_pos, runtime)?;
+        iteration += 1;
+        if iteration >= runtime.max_iterations {
+            return Err(EvalError::MaxIteration);
+        }
+    }
+
+    match program[block_end_pos] {
+        Token::END => Ok(block_end_pos + 1),
+        _ => Err(EvalError::Syntax(
+            block_end_pos,
+            format!("Expected END after a WHILE started at {while_pos}. Got to {block_end_pos}"),
+        )),
+    }
+}
+
+fn eval_stat(program: &Program, pos: usize, runtime: &mut Runtime) -> Result<usize, EvalError> {
+    log::trace!("eval stat {pos}");
+    match program[pos] {
+        Token::Stat(stat) => match stat {
+            Stat::OUTPUT => {
+                let (newpos, val) = eval_expr(program, pos + 1, runtime)?;
+                runtime.step()?;
+                runtime.write(val)?;
+                Ok(newpos)
+            }
+            Stat::INPUT => {
+                runtime.step()?;
+                let destination = match program[pos + 1] {
+                    Token::Reg(num) => num,
+                    _ => return Err(EvalError::Syntax(pos + 1, "Expected REG".into())),
+                };
+                let val = match runtime.next_input() {
+                    Some(val) => val,
+                    None => return Err(EvalError::Finished),
+                };
+                runtime.set_reg(destination, val)?;
+                Ok(pos + 2)
+            }
+            Stat::LOAD => {
+                let destination = match program[pos + 1] {
+                    Token::Reg(num) => num,
+                    _ => return Err(EvalError::Syntax(pos + 1, "Expected REG".into())),
+                };
+                let (newpos, val) = eval_expr(program, pos + 2, runtime)?;
+                runtime.step()?;
+                runtime.set_reg(destination, val)?;
+                Ok(newpos)
+            }
+            Stat::IF => {
+                let (true_block_pos, condition_val) = eval_expr(program, pos + 1, runtime)?;
+                runtime.step()?;
+                if is_truthy(condition_val) {
+                    log::trace!("IF condition at {pos} evaluated to TRUE");
+                    handle_if_true(program, true_block_pos, runtime)
+                } else {
+                    log::trace!("IF condition at {pos} evaluated to FALSE");
+                    handle_if_false(program, true_block_pos, runtime, pos)
+                }
+            }
+            Stat::WHILE => handle_while(program, pos, runtime),
+        },
+        _ => panic!("called eval_stat on non-stat at {pos}"),
+    }
+}
+
+fn eval_expr(
+    program: &Program,
+    pos: usize,
+    runtime: &mut Runtime,
+) -> Result<(usize, f32), EvalError> {
+    let opcode = program[pos];
+
+    match opcode {
+        Token::Expr(Expr::NUM(val)) => {
+            runtime.step()?;
+            Ok((pos + 1, val))
+        }
+        Token::Expr(func) => match operator(func) {
+            Operator::Unary(func) => {
+                let (pos, arg) = eval_expr(program, pos + 1, runtime)?;
+                runtime.step()?;
+                Ok((pos, func(arg)))
+            }
+            Operator::Binary(func) => {
+                let (pos, lhs) = eval_expr(program, pos + 1, runtime)?;
+                let (pos, rhs) = eval_expr(program, pos, runtime)?;
+                runtime.step()?;
+                Ok((pos, func(lhs, rhs)))
+            }
+        },
+        Token::Reg(num) => {
+            runtime.step()?;
+            Ok((pos + 1, runtime.read_reg(num)?))
+        }
+        _ => unreachable!("called eval_expr on non-expr: {opcode:?}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::growing::random_programs;
+    use super::*;
+    use crate::params::Params;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_stat_input() {
+        let program: Vec<Token> = vec![Token::Stat(Stat::INPUT), Token::Reg(0)];
+        let mut runtime = Runtime::new(2, vec![2.0]);
+        assert_eq!(runtime.memory, vec![0.0, 0.0]);
+        assert_eq!(runtime.input.len(), 1);
+        let res = eval_stat(&program, 0, &mut runtime);
+        assert!(res.is_ok());
+        assert_eq!(runtime.memory, vec![2.0, 0.0]);
+        let res = eval_stat(&program, 0, &mut runtime);
+        assert!(matches!(res, Err(EvalError::Finished)));
+    }
+
+    #[test]
+    fn test_stat_input_multiple() {
+        let program: Vec<Token> = vec![Token::Stat(Stat::INPUT), Token::Reg(0)];
+        let mut runtime = Runtime::new(2, vec![2.0, 3.0]);
+        assert_eq!(runtime.memory, vec![0.0, 0.0]);
+        assert_eq!(runtime.input.len(), 2);
+
+        let res = eval_stat(&program, 0, &mut runtime);
+        assert!(res.is_ok());
+        assert_eq!(runtime.memory, vec![2.0, 0.0]);
+
+        let res = eval_stat(&program, 0, &mut runtime);
+        assert!(res.is_ok());
+        assert_eq!(runtime.memory, vec![3.0, 0.0]);
+
+        let res = eval_stat(&program, 0, &mut runtime);
+        assert!(matches!(res, Err(EvalError::Finished)));
+    }
+
+    #[test]
+    fn test_stat_input_second_register() {
+        let program: Vec<Token> = vec![Token::Stat(Stat::INPUT), Token::Reg(1)];
+        let mut runtime = Runtime::new(2, vec![4.0]);
+        assert_eq!(runtime.memory, vec![0.0, 0.0]);
+        let res = eval_stat(&program, 0, &mut runtime);
+        assert!(res.is_ok());
+        assert_eq!(runtime.memory, vec![0.0, 4.0]);
+    }
+
+    #[test]
+    fn test_stat_output() {
+        let program: Vec<Token> = vec![Token::Stat(Stat::OUTPUT), Token::Reg(0)];
+        let mut runtime = Runtime {
+            memory: vec![2.0, 0.0],
+            input: vec![],
+            output: vec![],
+            input_cursor: 0,
+            max_iterations: 100,
+            max_steps: usize::MAX,
+            max_output_len: usize::MAX,
+            out_of_range: OutOfRange::Error,
+            steps: 0,
+        };
+        let res = eval_stat(&program, 0, &mut runtime);
+        assert!(res.is_ok());
+        assert_eq!(runtime.memory, vec![2.0, 0.0]);
+        assert_eq!(runtime.output, vec![2.0]);
+    }
+
+    #[test]
+    fn test_expression() {
+        let program: Vec<Token> = vec![
+            Token::Expr(Expr::ADD),
+            Token::Reg(0),
+            Token::Expr(Expr::DIV),
+            Token::Reg(1),
+            Token::Reg(1),
+        ];
+        let data = vec![1.0, -2.0];
+        let mut runtime = Runtime::new(3, vec![]);
+        runtime.memory = data;
+        let (pos, val) = eval_expr(&program, 0, &mut runtime).unwrap();
+        assert_eq!(5, pos);
+        assert_eq!(2.0, val);
+    }
+
+    #[test]
+    fn test_exec_identity() {
+        let program: Vec<Token> = vec![
+            Token::Stat(Stat::INPUT),
+            Token::Reg(0),
+            Token::Stat(Stat::OUTPUT),
+            Token::Reg(0),
+        ];
+        let mut runtime = Runtime::new(2, vec![2.0]);
+        let res = eval_stat(&program, 0, &mut runtime);
+        assert!(res.is_ok());
+        let res = eval_stat(&program, 2, &mut runtime);
+        assert!(res.is_ok());
+        assert_eq!(runtime.output, vec![2.0]);
+    }
+
+    // where the interpreter considers the node at pos to end
+    fn interpreted_node_end(program: &Program, pos: usize, memsize: usize) -> Option<usize> {
+        let mut runtime = Runtime::new(memsize, vec![]);
+        match program[pos] {
+            Token::Expr(_) | Token::Reg(_) => Some(eval_expr(program, pos, &mut runtime).ok()?.0),
+            Token::Stat(Stat::IF) => {
+                let (block_pos, _) = eval_expr(program, pos + 1, &mut runtime).ok()?;
+                let mut block_end = skip_block(program, block_pos);
+                if matches!(program[block_end], Token::ELSE) {
+                    block_end = skip_block(program, block_end + 1);
+                }
+                Some(block_end + 1)
+            }
+            Token::Stat(Stat::WHILE) => {
+                let (block_pos, _) = eval_expr(program, pos + 1, &mut runtime).ok()?;
+                Some(skip_block(program, block_pos) + 1)
+            }
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_get_node_end_matches_interpreter() {
+        let params = Params {
+            memsize: 3,
+            depth: 5,
+            ..Default::default()
+        };
+        for program in random_programs(3, 300, &params) {
+            for pos in 0..program.len() {
+                if let Some(end) = interpreted_node_end(&program, pos, params.memsize) {
+                    assert_eq!(
+                        get_node_end(&program, pos),
+                        end,
+                        "node at {pos} of {program:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_get_block_end_matches_interpreter() {
+        let params = Params {
+            memsize: 3,
+            depth: 5,
+            ..Default::default()
+        };
+        for program in random_programs(4, 300, &params) {
+            for pos in 0..program.len() {
+                if matches!(program[pos], Token::ELSE) {
+                    assert_eq!(
+                        get_block_end(&program, pos + 1),
+                        skip_block(&program, pos + 1)
+                    );
+                }
+            }
+            assert_eq!(get_block_end(&program, 0), program.len());
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use crate::params::{Metric, Params};
+
+use super::execution::Termination;
+
+// what running a program on a single case produced
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseResult {
+    pub error: f32,
+    pub output: Vec<f32>,
+    pub termination: Termination,
+    pub steps: usize,
+}
+
+// results of a program on every case, in the order of the cases
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Evaluation {
+    // aggregated error of the cases
+    pub error: f32,
+    pub cases: Vec<CaseResult>,
+}
+
+impl Evaluation {
+    // a program thrown out without running it
+    pub fn rejected() -> Self {
+        Evaluation {
+            error: f32::INFINITY,
+            cases: Vec::new(),
+        }
+    }
+
+    pub fn errors(&self) -> Vec<f32> {
+        self.cases.iter().map(|case| case.error).collect()
+    }
+
+    pub fn steps(&self) -> usize {
+        self.cases.iter().map(|case| case.steps).sum()
+    }
+
+    pub fn count(&self, termination: Termination) -> usize {
+        self.cases
+            .iter()
+            .filter(|case| case.termination == termination)
+            .count()
+    }
+}
+
+// the error of a program is 0 when it solves every case, its fitness is the negated error,
+// metrics are shared between the evaluation threads
+pub trait FitnessMetric: Sync {
+    // error of a single output compared to its target
+    fn output_error(&self, output: f32, target: f32, params: &Params) -> f32;
+
+    // combines the errors of all cases into the error of the program
+    fn aggregate(&self, case_errors: &[f32]) -> f32;
+
+    // outputs matched up with the targets, plus penalties for the unmatched ones
+    fn case_error(&self, output: &[f32], targets: &[f32], params: &Params) -> f32 {
+        let error: f32 = output
+            .iter()
+            .zip(targets)
+            .map(|(&output, &target)| self.output_error(output, target, params))
+            .sum();
+        let missing = targets.len().saturating_sub(output.len());
+        let extra = output.len().saturating_sub(targets.len());
+        error
+            + missing as f32 * params.missing_output_penalty
+            + extra as f32 * params.extra_output_penalty
+    }
+}
+
+// added to the error of a case on top of the metric, running out of input is how programs finish
+pub fn termination_penalty(termination: Termination, params: &Params) -> f32 {
+    match termination {
+        Termination::Finished => 0.0,
+        Termination::MaxIteration | Termination::StepLimit => params.max_iteration_penalty,
+        Termination::Semantic => params.semantic_error_penalty,
+        // outputs over the targets already cost EXTRA_OUTPUT_PENALTY
+        Termination::OutputLimit => 0.0,
+    }
+}
+
+pub struct SumAbs;
+pub struct MeanSquared;
+pub struct Rmse;
+pub struct MaxError;
+pub struct HitCount;
+pub struct RelativeError;
+
+impl FitnessMetric for SumAbs {
+    fn output_error(&self, output: f32, target: f32, _params: &Params) -> f32 {
+        (output - target).abs()
+    }
+
+    fn aggregate(&self, case_errors: &[f32]) -> f32 {
+        case_errors.iter().sum()
+    }
+}
+
+impl FitnessMetric for MeanSquared {
+    fn output_error(&self, output: f32, target: f32, _params: &Params) -> f32 {
+        (output - target).powi(2)
+    }
+
+    fn aggregate(&self, case_errors: &[f32]) -> f32 {
+        mean(case_errors)
+    }
+}
+
+impl FitnessMetric for Rmse {
+    fn output_error(&self, output: f32, target: f32, params: &Params) -> f32 {
+        MeanSquared.output_error(output, target, params)
+    }
+
+    fn aggregate(&self, case_errors: &[f32]) -> f32 {
+        MeanSquared.aggregate(case_errors).sqrt()
+    }
+}
+
+impl FitnessMetric for MaxError {
+    fn output_error(&self, output: f32, target: f32, params: &Params) -> f32 {
+        SumAbs.output_error(output, target, params)
+    }
+
+    // f32::max skips NaN, so a case without a valid error counts as the worst one
+    fn aggregate(&self, case_errors: &[f32]) -> f32 {
+        case_errors
+            .iter()
+            .map(|&error| if error.is_nan() { f32::INFINITY } else { error })
+            .fold(0.0, f32::max)
+    }
+}
+
+// the error is the number of misses, an unmatched output is always a miss
+impl FitnessMetric for HitCount {
+    fn output_error(&self, output: f32, target: f32, params: &Params) -> f32 {
+        if (output - target).abs() <= params.hit_tolerance {
+            0.0
+        } else {
+            1.0
+        }
+    }
+
+    fn aggregate(&self, case_errors: &[f32]) -> f32 {
+        case_errors.iter().sum()
+    }
+
+    fn case_error(&self, output: &[f32], targets: &[f32], params: &Params) -> f32 {
+        let misses = output
+            .iter()
+            .zip(targets)
+            .filter(|(&output, &target)| self.output_error(output, target, params) > 0.0)
+            .count();
+        (misses + output.len().abs_diff(targets.len())) as f32
+    }
+}
+
+// targets smaller than 1 are compared absolutely, so that they don't blow up the error
+impl FitnessMetric for RelativeError {
+    fn output_error(&self, output: f32, target: f32, _params: &Params) -> f32 {
+        (output - target).abs() / target.abs().max(1.0)
+    }
+
+    fn aggregate(&self, case_errors: &[f32]) -> f32 {
+        mean(case_errors)
+    }
+}
+
+fn mean(values: &[f32]) -> f32 {
+    if values.is_empty() {
+        0.0
+    } else {
+        values.iter().sum::<f32>() / values.len() as f32
+    }
+}
+
+pub fn metric(metric: Metric) -> &'static dyn FitnessMetric {
+    match metric {
+        Metric::SumAbs => &SumAbs,
+        Metric::MeanSquared => &MeanSquared,
+        Metric::Rmse => &Rmse,
+        Metric::MaxError => &MaxError,
+        Metric::HitCount => &HitCount,
+        Metric::RelativeError => &RelativeError,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_case_error_over_all_outputs() {
+        let params = Params {
+            missing_output_penalty: 100.0,
+            extra_output_penalty: 10.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            SumAbs.case_error(&[1.0, 4.0, 9.0], &[1.0, 4.0, 9.0], &params),
+            0.0
+        );
+        assert_eq!(
+            SumAbs.case_error(&[1.0, 5.0, 7.0], &[1.0, 4.0, 9.0], &params),
+            3.0
+        );
+        assert_eq!(SumAbs.case_error(&[1.0], &[1.0, 4.0, 9.0], &params), 200.0);
+        assert_eq!(
+            SumAbs.case_error(&[1.0, 4.0, 9.0, 16.0], &[1.0, 4.0, 9.0], &params),
+            10.0
+        );
+        assert_eq!(SumAbs.case_error(&[], &[], &params), 0.0);
+    }
+
+    #[test]
+    fn test_metrics() {
+        let params = Params {
+            hit_tolerance: 0.5,
+            ..Default::default()
+        };
+        let cases = [
+            (vec![1.0], vec![2.0]),
+            (vec![10.0], vec![7.0]),
+            (vec![0.1], vec![0.0]),
+        ];
+        let error = |m: Metric| {
+            let metric = metric(m);
+            let errors: Vec<f32> = cases
+                .iter()
+                .map(|(output, targets)| metric.case_error(output, targets, &params))
+                .collect();
+            metric.aggregate(&errors)
+        };
+        assert!((error(Metric::SumAbs) - 4.1).abs() < 1e-5);
+        assert!((error(Metric::MeanSquared) - 10.01 / 3.0).abs() < 1e-5);
+        assert!((error(Metric::Rmse) - (10.01f32 / 3.0).sqrt()).abs() < 1e-5);
+        assert_eq!(error(Metric::MaxError), 3.0);
+        assert_eq!(error(Metric::HitCount), 2.0);
+        assert!((error(Metric::RelativeError) - (0.5 + 3.0 / 7.0 + 0.1) / 3.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_hit_count_misses_unmatched_outputs() {
+        let params = Params::default();
+        assert_eq!(HitCount.case_error(&[1.0, 2.0], &[1.0, 2.0], &params), 0.0);
+        assert_eq!(HitCount.case_error(&[1.0], &[1.0, 2.0, 3.0], &params), 2.0);
+        assert_eq!(HitCount.case_error(&[1.0, 2.0, 3.0], &[1.0], &params), 2.0);
+        assert_eq!(HitCount.case_error(&[f32::NAN], &[1.0], &params), 1.0);
+    }
+
+    #[test]
+    fn test_max_error_of_nan_is_infinite() {
+        let params = Params::default();
+        let errors = [MaxError.case_error(&[f32::NAN], &[1.0], &params), 7.0];
+        assert_eq!(MaxError.aggregate(&errors), f32::INFINITY);
+        assert_eq!(MaxError.aggregate(&[f32::NAN, f32::NAN]), f32::INFINITY);
+        assert_eq!(MaxError.aggregate(&[]), 0.0);
+    }
+}
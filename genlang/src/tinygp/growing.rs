@@ -3,25 +3,231 @@ use crate::params::Params;
 use super::common::*;
 use rand::prelude::*;
 
-pub fn grow_stat(program: &mut Program, depth: usize, params: &Params, rand: &mut StdRng) -> bool {
-    if program.len() >= MAX_LEN || depth > params.depth {
+// maximum number of statements grown into a single block
+const MAX_BLOCK_LEN: usize = 3;
+
+// chance that Grow stops an expression at a terminal, picking from all the primitives instead
+// would almost always pick an operator and grow trees nearly as deep as Full
+const GROW_TERMINAL_PROB: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Method {
+    // every branch reaches the depth limit
+    Full,
+    // terminals can be picked at any depth
+    Grow,
+}
+
+// `depth` is the remaining depth budget, a statement on depth 1 can not contain nested blocks
+pub fn grow_stat(
+    program: &mut Program,
+    depth: usize,
+    method: Method,
+    params: &Params,
+    rand: &mut StdRng,
+) -> bool {
+    if program.len() >= MAX_LEN || depth == 0 {
         return false;
     }
-    let stat = rand.gen();
+    let stat = match method {
+        _ if depth <= 1 => *[Stat::INPUT, Stat::OUTPUT, Stat::LOAD]
+            .choose(rand)
+            .unwrap(),
+        Method::Full => *[Stat::IF, Stat::WHILE].choose(rand).unwrap(),
+        Method::Grow => rand.gen(),
+    };
     program.push(Token::Stat(stat));
     match stat {
+        Stat::INPUT => {
+            grow_reg(program, params, rand);
+        }
         Stat::OUTPUT => {
-            let regnum = rand.gen_range(0, params.memsize);
-            let reg = Token::Reg(regnum);
-            program.push(reg);
+            grow_expr(program, depth, method, params, rand);
+        }
+        Stat::LOAD => {
+            grow_reg(program, params, rand);
+            grow_expr(program, depth, method, params, rand);
+        }
+        Stat::IF => {
+            grow_expr(program, depth - 1, method, params, rand);
+            grow_block(program, depth - 1, method, params, rand);
+            if rand.gen_bool(0.5) {
+                program.push(Token::ELSE);
+                grow_block(program, depth - 1, method, params, rand);
+            }
+            program.push(Token::END);
+        }
+        Stat::WHILE => {
+            grow_expr(program, depth - 1, method, params, rand);
+            grow_block(program, depth - 1, method, params, rand);
+            program.push(Token::END);
         }
-        Stat::INPUT => {
-            let regnum = rand.gen_range(0, params.memsize);
-            let reg = Token::Reg(regnum);
-            program.push(reg);
-        },
-        _ => todo!()
     }
     log::trace!("grew into {:?}", program);
-    return true;
-}
\ No newline at end of file
+    true
+}
+
+pub fn grow_block(
+    program: &mut Program,
+    depth: usize,
+    method: Method,
+    params: &Params,
+    rand: &mut StdRng,
+) {
+    let len = rand.gen_range(1, MAX_BLOCK_LEN + 1);
+    for _ in 0..len {
+        if !grow_stat(program, depth, method, params, rand) {
+            break;
+        }
+    }
+}
+
+pub fn grow_expr(
+    program: &mut Program,
+    depth: usize,
+    method: Method,
+    params: &Params,
+    rand: &mut StdRng,
+) {
+    // an expression can't be cut short, so close to MAX_LEN only terminals are grown
+    if depth <= 1 || program.len() + depth >= MAX_LEN {
+        grow_terminal(program, params, rand);
+        return;
+    }
+    if method == Method::Grow && rand.gen_bool(GROW_TERMINAL_PROB) {
+        grow_terminal(program, params, rand);
+        return;
+    }
+    let expr = loop {
+        match rand.gen() {
+            Expr::NUM(_) => continue,
+            e => break e,
+        }
+    };
+    program.push(Token::Expr(expr));
+    for _ in 0..expr.argnum() {
+        grow_expr(program, depth - 1, method, params, rand);
+    }
+}
+
+pub fn grow_terminal(program: &mut Program, params: &Params, rand: &mut StdRng) {
+    if rand.gen_bool(0.5) {
+        grow_reg(program, params, rand);
+    } else {
+        program.push(Token::Expr(Expr::NUM(random_num(params, rand))));
+    }
+}
+
+fn random_num(params: &Params, rand: &mut StdRng) -> f32 {
+    if params.min_random < params.max_random {
+        rand.gen_range(params.min_random, params.max_random)
+    } else {
+        params.min_random
+    }
+}
+
+pub fn grow_reg(program: &mut Program, params: &Params, rand: &mut StdRng) {
+    let regnum = rand.gen_range(0, params.memsize);
+    program.push(Token::Reg(regnum));
+}
+
+// programs grown to params.depth with a randomly picked method, the same ones for the same seed
+#[cfg(test)]
+pub fn random_programs(seed: u64, n: usize, params: &Params) -> Vec<Program> {
+    let mut rand = StdRng::seed_from_u64(seed);
+    (0..n)
+        .map(|_| {
+            let mut program = vec![];
+            let method = *[Method::Full, Method::Grow].choose(&mut rand).unwrap();
+            grow_block(&mut program, params.depth, method, params, &mut rand);
+            program
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::execution::{reference::execute, Runtime};
+    use super::*;
+
+    #[test]
+    fn test_grown_programs_execute() {
+        let params = Params {
+            memsize: 3,
+            depth: 4,
+            ..Default::default()
+        };
+        let mut rand = StdRng::seed_from_u64(0);
+        for _ in 0..500 {
+            let mut program = vec![];
+            assert!(grow_stat(
+                &mut program,
+                params.depth,
+                Method::Grow,
+                &params,
+                &mut rand
+            ));
+            assert!(required_depth(&program) <= params.depth, "{program:?}");
+            // execute panics on syntax errors
+            execute(&program, Runtime::new(params.memsize, vec![1.0, 2.0, 3.0]));
+        }
+    }
+
+    #[test]
+    fn test_grow_uses_whole_grammar() {
+        let params = Params {
+            memsize: 2,
+            depth: 3,
+            ..Default::default()
+        };
+        let mut rand = StdRng::seed_from_u64(1);
+        let mut program = vec![];
+        for _ in 0..200 {
+            grow_stat(&mut program, params.depth, Method::Grow, &params, &mut rand);
+        }
+        for stat in [Stat::INPUT, Stat::OUTPUT, Stat::LOAD, Stat::IF, Stat::WHILE] {
+            assert!(program.contains(&Token::Stat(stat)), "{stat:?} never grown");
+        }
+        assert!(program.contains(&Token::ELSE));
+        assert!(program
+            .iter()
+            .any(|t| matches!(t, Token::Expr(e) if e.argnum() == 2)));
+        assert!(program
+            .iter()
+            .any(|t| matches!(t, Token::Expr(Expr::NUM(_)))));
+    }
+
+    #[test]
+    fn test_grow_spreads_expression_depths() {
+        let params = Params {
+            memsize: 2,
+            depth: 5,
+            ..Default::default()
+        };
+        let mut rand = StdRng::seed_from_u64(3);
+        let mut counts = [0; 6];
+        for _ in 0..1000 {
+            let mut program = vec![];
+            grow_expr(&mut program, params.depth, Method::Grow, &params, &mut rand);
+            counts[required_depth(&program)] += 1;
+        }
+        // every depth up to the limit, most of the trees stopping short of it
+        assert!(counts[1..].iter().all(|&count| count > 0), "{counts:?}");
+        assert!(counts[..5].iter().sum::<i32>() > counts[5], "{counts:?}");
+    }
+
+    #[test]
+    fn test_full_reaches_depth() {
+        let params = Params {
+            memsize: 2,
+            depth: 3,
+            ..Default::default()
+        };
+        let mut rand = StdRng::seed_from_u64(2);
+        for _ in 0..50 {
+            let mut program = vec![];
+            grow_stat(&mut program, params.depth, Method::Full, &params, &mut rand);
+            assert_eq!(required_depth(&program), params.depth, "{program:?}");
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use super::{
     common::*,
-    execution::{execute, Runtime},
+    execution::{reference::execute, Runtime},
 };
 
 const INPUT: Token = Token::Stat(Stat::INPUT);
@@ -23,7 +23,7 @@ fn run_cases(program: &Program, memsize: usize, cases: Vec<(Vec<f32>, Vec<f32>)>
     for (i, (input, expected_output)) in cases.into_iter().enumerate() {
         let runtime = Runtime::new(memsize, input);
         println!("\nCase {i}");
-        let output = execute(&program, runtime);
+        let output = execute(program, runtime).output;
         assert_eq!(output, expected_output);
     }
 }
@@ -280,7 +280,7 @@ fn test_while_interrupt_infinite_loop() {
     ];
     let runtime = Runtime::new(memsize, vec![]);
     println!("Entering infinite loop");
-    let output = execute(&program, runtime);
+    let output = execute(&program, runtime).output;
     assert!(output.len() > 3);
     assert_eq!(output[0], 1.0);
     assert_eq!(output[1], 2.0);
@@ -463,4 +463,85 @@ fn test_expr_not() {
         (vec![1.0], vec![0.0]),
     ];
     run_cases(&program, memsize, cases);
-}
\ No newline at end of file
+}
+#[test]
+#[rustfmt::skip]
+fn test_expr_unary_math() {
+    let memsize = 3;
+    let program = vec![
+        INPUT, Reg(0),
+        OUTPUT, Token::Expr(Expr::EXP), Reg(0),
+        OUTPUT, Token::Expr(Expr::ABS), Reg(0),
+        OUTPUT, Token::Expr(Expr::NEG), Reg(0),
+        OUTPUT, Token::Expr(Expr::FLOOR), Reg(0),
+        OUTPUT, Token::Expr(Expr::TANH), Reg(0),
+    ];
+    let cases: Vec<(Vec<f32>, Vec<f32>)> = vec![
+        (vec![0.0], vec![1.0, 0.0, -0.0, 0.0, 0.0]),
+        (vec![-1.5], vec![(-1.5f32).exp(), 1.5, 1.5, -2.0, (-1.5f32).tanh()]),
+    ];
+    run_cases(&program, memsize, cases);
+}
+#[test]
+#[rustfmt::skip]
+fn test_expr_binary_math() {
+    let memsize = 3;
+    let program = vec![
+        INPUT, Reg(0),
+        INPUT, Reg(1),
+        OUTPUT, Token::Expr(Expr::POW), Reg(0), Reg(1),
+        OUTPUT, Token::Expr(Expr::MIN), Reg(0), Reg(1),
+        OUTPUT, Token::Expr(Expr::MAX), Reg(0), Reg(1),
+        OUTPUT, Token::Expr(Expr::MOD), Reg(0), Reg(1),
+    ];
+    let cases: Vec<(Vec<f32>, Vec<f32>)> = vec![
+        (vec![2.0, 3.0], vec![8.0, 2.0, 3.0, 2.0]),
+        (vec![-7.0, 2.0], vec![49.0, -7.0, 2.0, -1.0]),
+    ];
+    run_cases(&program, memsize, cases);
+}
+#[test]
+#[rustfmt::skip]
+fn test_expr_protected() {
+    let memsize = 3;
+    let program = vec![
+        INPUT, Reg(0),
+        INPUT, Reg(1),
+        OUTPUT, Token::Expr(Expr::LOG), Reg(0),
+        OUTPUT, Token::Expr(Expr::SQRT), Reg(0),
+        OUTPUT, Token::Expr(Expr::EXP), Reg(0),
+        OUTPUT, Token::Expr(Expr::POW), Reg(0), Reg(1),
+        OUTPUT, Token::Expr(Expr::MOD), Reg(0), Reg(1),
+    ];
+    let cases: Vec<(Vec<f32>, Vec<f32>)> = vec![
+        (vec![0.0, -1.0], vec![0.0, 0.0, 1.0, 0.0, 0.0]),
+        (vec![-9.0, 0.0], vec![9f32.ln(), 3.0, (-9f32).exp(), 1.0, -9.0]),
+        (vec![1000.0, 100.0], vec![1000f32.ln(), 1000f32.sqrt(), 1000.0, 1000.0, 0.0]),
+    ];
+    run_cases(&program, memsize, cases);
+}
+
+#[test]
+#[rustfmt::skip]
+fn test_skip_block_starting_with_nested_block() {
+    let memsize = 3;
+    let program = vec![
+        INPUT, Reg(0),
+        IF, Reg(0),
+            WHILE, num(1.0),
+                OUTPUT, num(2.0),
+            END,
+        ELSE,
+            IF, num(1.0),
+                OUTPUT, num(3.0),
+            ELSE,
+                OUTPUT, num(4.0),
+            END,
+        END,
+        OUTPUT, num(5.0),
+    ];
+    let cases: Vec<(Vec<f32>, Vec<f32>)> = vec![
+        (vec![0.0], vec![3.0, 5.0]),
+    ];
+    run_cases(&program, memsize, cases);
+}
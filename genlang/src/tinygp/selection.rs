@@ -0,0 +1,386 @@
+use crate::params::{Params, SelectionScheme};
+
+use super::fitness::Evaluation;
+
+use rand::prelude::*;
+
+// picks the index of a parent, `evaluations` hold the per-case results of every individual
+pub trait Selection {
+    // whatever the scheme needs to know about the whole population, computed once
+    // before the picks of a generation instead of on every pick
+    fn prepare(&self, _fitness: &[f32], _evaluations: &[Evaluation]) -> Prepared {
+        Prepared::default()
+    }
+
+    fn select(
+        &self,
+        fitness: &[f32],
+        evaluations: &[Evaluation],
+        prepared: &Prepared,
+        params: &Params,
+        rand: &mut StdRng,
+    ) -> usize;
+}
+
+// prepared again whenever the population changes, so that replaced individuals get their own weights
+#[derive(Debug, Clone, Default)]
+pub struct Prepared {
+    // chances of every individual to be picked, used by roulette and rank
+    weights: Vec<f32>,
+    // how far from the best error of each case an individual may be to survive it
+    epsilons: Vec<f32>,
+}
+
+pub struct Tournament;
+pub struct Roulette;
+pub struct Rank;
+pub struct Lexicase;
+pub struct EpsilonLexicase;
+
+impl Selection for Tournament {
+    fn select(
+        &self,
+        fitness: &[f32],
+        _evaluations: &[Evaluation],
+        _prepared: &Prepared,
+        params: &Params,
+        rand: &mut StdRng,
+    ) -> usize {
+        tournament(fitness, params.tournament_size, rand)
+    }
+}
+
+// fitness proportional, an error e is weighted as 1 / (1 + e)
+impl Selection for Roulette {
+    fn prepare(&self, fitness: &[f32], _evaluations: &[Evaluation]) -> Prepared {
+        let weights = fitness
+            .iter()
+            .map(|&f| match 1.0 / (1.0 - f) {
+                w if w.is_finite() && w > 0.0 => w,
+                _ => 0.0,
+            })
+            .collect();
+        Prepared {
+            weights,
+            ..Default::default()
+        }
+    }
+
+    fn select(
+        &self,
+        _fitness: &[f32],
+        _evaluations: &[Evaluation],
+        prepared: &Prepared,
+        _params: &Params,
+        rand: &mut StdRng,
+    ) -> usize {
+        spin(&prepared.weights, rand)
+    }
+}
+
+// linear ranking, the worst individual has weight 1 and the best has weight popsize
+impl Selection for Rank {
+    fn prepare(&self, fitness: &[f32], _evaluations: &[Evaluation]) -> Prepared {
+        let mut ranking: Vec<usize> = (0..fitness.len()).collect();
+        ranking.sort_by(|&a, &b| rank_key(fitness[a]).total_cmp(&rank_key(fitness[b])));
+        let mut weights = vec![0.0; fitness.len()];
+        for (rank, &i) in ranking.iter().enumerate() {
+            weights[i] = (rank + 1) as f32;
+        }
+        Prepared {
+            weights,
+            ..Default::default()
+        }
+    }
+
+    fn select(
+        &self,
+        _fitness: &[f32],
+        _evaluations: &[Evaluation],
+        prepared: &Prepared,
+        _params: &Params,
+        rand: &mut StdRng,
+    ) -> usize {
+        spin(&prepared.weights, rand)
+    }
+}
+
+impl Selection for Lexicase {
+    fn select(
+        &self,
+        _fitness: &[f32],
+        evaluations: &[Evaluation],
+        _prepared: &Prepared,
+        _params: &Params,
+        rand: &mut StdRng,
+    ) -> usize {
+        lexicase(evaluations, |_| 0.0, rand)
+    }
+}
+
+// lexicase where everything within the median absolute deviation of the best error survives a case
+impl Selection for EpsilonLexicase {
+    fn prepare(&self, _fitness: &[f32], evaluations: &[Evaluation]) -> Prepared {
+        let epsilons = (0..case_count(evaluations))
+            .map(|case| {
+                let case_errors: Vec<f32> = evaluations
+                    .iter()
+                    .map(|evaluation| case_error(evaluation, case))
+                    .collect();
+                let middle = median(case_errors.clone());
+                let deviation = median(case_errors.iter().map(|e| (e - middle).abs()).collect());
+                // infinite errors leave no usable deviation
+                if deviation.is_nan() {
+                    0.0
+                } else {
+                    deviation
+                }
+            })
+            .collect();
+        Prepared {
+            epsilons,
+            ..Default::default()
+        }
+    }
+
+    fn select(
+        &self,
+        _fitness: &[f32],
+        evaluations: &[Evaluation],
+        prepared: &Prepared,
+        _params: &Params,
+        rand: &mut StdRng,
+    ) -> usize {
+        lexicase(
+            evaluations,
+            |case| prepared.epsilons.get(case).copied().unwrap_or(0.0),
+            rand,
+        )
+    }
+}
+
+pub fn selection(scheme: SelectionScheme) -> &'static dyn Selection {
+    match scheme {
+        SelectionScheme::Tournament => &Tournament,
+        SelectionScheme::Roulette => &Roulette,
+        SelectionScheme::Rank => &Rank,
+        SelectionScheme::Lexicase => &Lexicase,
+        SelectionScheme::EpsilonLexicase => &EpsilonLexicase,
+    }
+}
+
+// best of `tournament_size` randomly drawn competitors
+pub fn tournament(fitness: &[f32], tournament_size: usize, rand: &mut StdRng) -> usize {
+    let mut best = rand.gen_range(0, fitness.len());
+    let mut best_fitness = rank_key(fitness[best]);
+
+    for _ in 1..tournament_size {
+        let competitor = rand.gen_range(0, fitness.len());
+        if rank_key(fitness[competitor]) > best_fitness {
+            best_fitness = rank_key(fitness[competitor]);
+            best = competitor;
+        }
+    }
+    best
+}
+
+// worst of `tournament_size` randomly drawn competitors
+pub fn negative_tournament(fitness: &[f32], tournament_size: usize, rand: &mut StdRng) -> usize {
+    let mut worst = rand.gen_range(0, fitness.len());
+    let mut worst_fitness = rank_key(fitness[worst]);
+
+    for _ in 1..tournament_size {
+        let competitor = rand.gen_range(0, fitness.len());
+        if rank_key(fitness[competitor]) < worst_fitness {
+            worst_fitness = rank_key(fitness[competitor]);
+            worst = competitor;
+        }
+    }
+    worst
+}
+
+// cases are visited in random order, each one keeps the candidates within `epsilon(case)` of the best
+fn lexicase(
+    evaluations: &[Evaluation],
+    epsilon: impl Fn(usize) -> f32,
+    rand: &mut StdRng,
+) -> usize {
+    let error = |i: usize, case: usize| case_error(&evaluations[i], case);
+    let mut candidates: Vec<usize> = (0..evaluations.len()).collect();
+    let mut cases: Vec<usize> = (0..case_count(evaluations)).collect();
+    cases.shuffle(rand);
+
+    for case in cases {
+        if candidates.len() <= 1 {
+            break;
+        }
+        let best = candidates
+            .iter()
+            .map(|&i| error(i, case))
+            .fold(f32::INFINITY, f32::min);
+        let threshold = best + epsilon(case);
+        candidates.retain(|&i| error(i, case) <= threshold);
+    }
+    *candidates.choose(rand).unwrap()
+}
+
+// index drawn with probability proportional to its weight, uniformly if all weights are 0
+fn spin(weights: &[f32], rand: &mut StdRng) -> usize {
+    let total: f32 = weights.iter().sum();
+    if total <= 0.0 || !total.is_finite() {
+        return rand.gen_range(0, weights.len());
+    }
+    let mut ball = rand.gen_range(0.0, total);
+    for (i, &weight) in weights.iter().enumerate() {
+        if ball < weight {
+            return i;
+        }
+        ball -= weight;
+    }
+    // rounding can leave the ball past the last pocket
+    weights.iter().rposition(|&w| w > 0.0).unwrap()
+}
+
+// NaN fitness ranks last
+pub fn rank_key(fitness: f32) -> f32 {
+    if fitness.is_nan() {
+        f32::NEG_INFINITY
+    } else {
+        fitness
+    }
+}
+
+// NaN error is worse than any other, rejected programs have no case results
+fn case_error(evaluation: &Evaluation, case: usize) -> f32 {
+    match evaluation.cases.get(case) {
+        Some(result) if !result.error.is_nan() => result.error,
+        _ => f32::INFINITY,
+    }
+}
+
+// rejected programs have no case results, so the cases are counted over the whole population
+fn case_count(evaluations: &[Evaluation]) -> usize {
+    evaluations.iter().map(|e| e.cases.len()).max().unwrap_or(0)
+}
+
+fn median(mut values: Vec<f32>) -> f32 {
+    values.sort_by(f32::total_cmp);
+    values[values.len() / 2]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::execution::Termination;
+    use super::super::fitness::CaseResult;
+    use super::*;
+
+    fn evaluation(errors: &[f32]) -> Evaluation {
+        Evaluation {
+            error: errors.iter().sum(),
+            cases: errors
+                .iter()
+                .map(|&error| CaseResult {
+                    error,
+                    output: vec![],
+                    termination: Termination::Finished,
+                    steps: 0,
+                })
+                .collect(),
+        }
+    }
+
+    fn count_picks(selection: &dyn Selection, fitness: &[f32], errors: &[Vec<f32>]) -> Vec<usize> {
+        let params = Params {
+            tournament_size: 2,
+            ..Default::default()
+        };
+        let evaluations: Vec<Evaluation> = errors.iter().map(|errors| evaluation(errors)).collect();
+        let prepared = selection.prepare(fitness, &evaluations);
+        let mut rand = StdRng::seed_from_u64(0);
+        let mut counts = vec![0; fitness.len()];
+        for _ in 0..4000 {
+            counts[selection.select(fitness, &evaluations, &prepared, &params, &mut rand)] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn test_tournament_size() {
+        let fitness = [0.0, -1.0, -2.0, -3.0];
+        let mut rand = StdRng::seed_from_u64(0);
+        // a single competitor wins its own tournament
+        let picks: Vec<usize> = (0..4000)
+            .map(|_| tournament(&fitness, 1, &mut rand))
+            .collect();
+        assert!(picks.iter().filter(|&&i| i == 3).count() > 800);
+        let picks: Vec<usize> = (0..4000)
+            .map(|_| negative_tournament(&fitness, 1, &mut rand))
+            .collect();
+        assert!(picks.iter().filter(|&&i| i == 0).count() > 800);
+        // of two competitors the worst one wins only if drawn twice
+        let picks: Vec<usize> = (0..4000)
+            .map(|_| tournament(&fitness, 2, &mut rand))
+            .collect();
+        let worst = picks.iter().filter(|&&i| i == 3).count();
+        assert!(worst > 100 && worst < 400, "{worst}");
+    }
+
+    #[test]
+    fn test_fitness_based_selection_prefers_better() {
+        let fitness = [-10.0, 0.0, -1.0, f32::NAN];
+        for scheme in [
+            SelectionScheme::Tournament,
+            SelectionScheme::Roulette,
+            SelectionScheme::Rank,
+        ] {
+            let counts = count_picks(selection(scheme), &fitness, &[]);
+            assert!(counts[1] > counts[2], "{scheme:?} {counts:?}");
+            assert!(counts[2] > counts[0], "{scheme:?} {counts:?}");
+            assert!(counts[0] >= counts[3], "{scheme:?} {counts:?}");
+        }
+    }
+
+    #[test]
+    fn test_prepare() {
+        let fitness = [-10.0, 0.0, f32::NAN, -1.0];
+        assert_eq!(Rank.prepare(&fitness, &[]).weights, [2.0, 4.0, 1.0, 3.0]);
+        assert_eq!(
+            Roulette.prepare(&fitness, &[]).weights,
+            [1.0 / 11.0, 1.0, 0.0, 0.5]
+        );
+
+        let evaluations = [
+            evaluation(&[0.0, 1.0]),
+            evaluation(&[2.0, 1.0]),
+            evaluation(&[4.0, f32::INFINITY]),
+            Evaluation::rejected(),
+        ];
+        let prepared = EpsilonLexicase.prepare(&[], &evaluations);
+        assert_eq!(prepared.epsilons, [4.0, 0.0]);
+    }
+
+    #[test]
+    fn test_lexicase_picks_specialists() {
+        // 0 and 1 each solve a case perfectly, 2 is the best on average, 3 is never the best
+        let errors = vec![
+            vec![0.0, 10.0],
+            vec![10.0, 0.0],
+            vec![2.0, 2.0],
+            vec![3.0, 3.0],
+        ];
+        let fitness: Vec<f32> = errors.iter().map(|e| -e.iter().sum::<f32>()).collect();
+        let counts = count_picks(&Lexicase, &fitness, &errors);
+        assert_eq!(counts[2] + counts[3], 0);
+        assert!(counts[0] > 1000 && counts[1] > 1000, "{counts:?}");
+
+        let errors = vec![
+            vec![0.0, 5.0],
+            vec![0.1, 0.0],
+            vec![5.0, 5.0],
+            vec![5.0, 5.0],
+        ];
+        // the deviation of the first case is large enough to keep 1 in the race
+        let counts = count_picks(&EpsilonLexicase, &fitness, &errors);
+        assert_eq!(counts[0] + counts[2] + counts[3], 0, "{counts:?}");
+    }
+}
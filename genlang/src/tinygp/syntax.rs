@@ -0,0 +1,446 @@
+// text form of programs, following jezyk.txt:
+//
+// INPUT R0
+// WHILE GT R0 0 DO
+//   OUTPUT MUL R0 R0
+//   LOAD R0 SUB R0 1
+// END
+//
+// keywords are case insensitive and `#` starts a comment running to the end of the line
+
+use std::{error::Error, fmt::Display, ops::Range};
+
+use super::common::*;
+
+const INDENT: &str = "  ";
+
+pub fn pprint(program: &Program) -> String {
+    let mut text = String::new();
+    let mut indent = 0;
+    let mut pos = 0;
+    let new_line = |text: &mut String, indent: usize| {
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        text.push_str(&INDENT.repeat(indent));
+    };
+    while pos < program.len() {
+        match program[pos] {
+            Token::Stat(stat) => {
+                new_line(&mut text, indent);
+                text.push_str(&format!("{stat:?}"));
+                if let Stat::IF | Stat::WHILE = stat {
+                    let condition_end = get_node_end(program, pos + 1).min(program.len());
+                    for token in &program[pos + 1..condition_end] {
+                        text.push(' ');
+                        text.push_str(&word(token));
+                    }
+                    if stat == Stat::WHILE {
+                        text.push_str(" DO");
+                    }
+                    indent += 1;
+                    pos = condition_end;
+                    continue;
+                }
+            }
+            Token::ELSE => {
+                new_line(&mut text, indent.saturating_sub(1));
+                text.push_str("ELSE");
+            }
+            Token::END => {
+                indent = indent.saturating_sub(1);
+                new_line(&mut text, indent);
+                text.push_str("END");
+            }
+            token => {
+                text.push(' ');
+                text.push_str(&word(&token));
+            }
+        }
+        pos += 1;
+    }
+    text
+}
+
+fn word(token: &Token) -> String {
+    match token {
+        Token::Expr(Expr::NUM(val)) => val.to_string(),
+        Token::Expr(expr) => format!("{expr:?}"),
+        Token::Stat(stat) => format!("{stat:?}"),
+        Token::Reg(num) => format!("R{num}"),
+        Token::ELSE => "ELSE".to_owned(),
+        Token::END => "END".to_owned(),
+    }
+}
+
+pub fn parse(text: &str) -> Result<Program, SyntaxError> {
+    parse_words(text, split_words(text))
+}
+
+// parses words already split out of `text`, lets other frontends share the grammar
+pub fn parse_words<'a>(
+    text: &'a str,
+    words: Vec<(Range<usize>, &'a str)>,
+) -> Result<Program, SyntaxError> {
+    let mut parser = Parser {
+        text,
+        words,
+        pos: 0,
+        program: Vec::new(),
+    };
+    parser.block(None)?;
+    Ok(parser.program)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    // byte range of the offending word in the parsed text
+    pub span: Range<usize>,
+    pub line: usize,
+    pub column: usize,
+    pub reason: String,
+    source_line: String,
+}
+
+impl SyntaxError {
+    pub fn new(text: &str, span: Range<usize>, reason: String) -> Self {
+        let (line, column) = location(text, span.start);
+        let source_line = text.lines().nth(line - 1).unwrap_or("").to_owned();
+        SyntaxError {
+            span,
+            line,
+            column,
+            reason,
+            source_line,
+        }
+    }
+}
+
+impl Display for SyntaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let width = self.span.len().max(1);
+        writeln!(f, "{}:{}: {}", self.line, self.column, self.reason)?;
+        writeln!(f, "{}", self.source_line)?;
+        write!(f, "{}{}", " ".repeat(self.column - 1), "^".repeat(width))
+    }
+}
+
+impl Error for SyntaxError {}
+
+// words of the text with their byte ranges, comments left out
+fn split_words(text: &str) -> Vec<(Range<usize>, &str)> {
+    let mut words = Vec::new();
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let code = line.split('#').next().unwrap();
+        let mut start = None;
+        for (i, c) in code.char_indices().chain([(code.len(), ' ')]) {
+            match (start, c.is_whitespace()) {
+                (None, false) => start = Some(i),
+                (Some(s), true) => {
+                    words.push((offset + s..offset + i, &code[s..i]));
+                    start = None;
+                }
+                _ => (),
+            }
+        }
+        offset += line.len();
+    }
+    words
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    words: Vec<(Range<usize>, &'a str)>,
+    pos: usize,
+    program: Program,
+}
+
+impl<'a> Parser<'a> {
+    // statements up to the closing word, returns the index of the closing word
+    fn block(&mut self, opened_by: Option<usize>) -> Result<usize, SyntaxError> {
+        loop {
+            let Some((_, word)) = self.words.get(self.pos) else {
+                return match opened_by {
+                    Some(opening) => {
+                        let (line, column) = location(self.text, self.words[opening].0.start);
+                        let keyword = self.words[opening].1.to_uppercase();
+                        Err(self.error_at(
+                            opening,
+                            format!("{keyword} at {line}:{column} is never closed with END"),
+                        ))
+                    }
+                    None => Ok(self.pos),
+                };
+            };
+            match word.to_uppercase().as_str() {
+                "ELSE" | "END" if opened_by.is_some() => return Ok(self.pos),
+                "ELSE" | "END" | "DO" => {
+                    return Err(self.error_at(self.pos, format!("Unexpected {word}")))
+                }
+                _ => self.stat()?,
+            }
+        }
+    }
+
+    fn stat(&mut self) -> Result<(), SyntaxError> {
+        let start = self.pos;
+        let word = self.words[self.pos].1;
+        self.pos += 1;
+        let stat = match word.to_uppercase().as_str() {
+            "INPUT" => Stat::INPUT,
+            "OUTPUT" => Stat::OUTPUT,
+            "LOAD" => Stat::LOAD,
+            "IF" => Stat::IF,
+            "WHILE" => Stat::WHILE,
+            _ => {
+                return Err(self.error_at(start, format!("Expected a statement, got '{word}'")));
+            }
+        };
+        self.program.push(Token::Stat(stat));
+        match stat {
+            Stat::INPUT => self.reg()?,
+            Stat::OUTPUT => self.expr()?,
+            Stat::LOAD => {
+                self.reg()?;
+                self.expr()?;
+            }
+            Stat::IF => {
+                self.expr()?;
+                let closing = self.block(Some(start))?;
+                if self.words[closing].1.eq_ignore_ascii_case("ELSE") {
+                    self.pos += 1;
+                    self.program.push(Token::ELSE);
+                    self.block(Some(start))?;
+                    self.expect("END")?;
+                } else {
+                    self.pos += 1;
+                }
+                self.program.push(Token::END);
+            }
+            Stat::WHILE => {
+                self.expr()?;
+                self.expect("DO")?;
+                self.block(Some(start))?;
+                self.expect("END")?;
+                self.program.push(Token::END);
+            }
+        }
+        Ok(())
+    }
+
+    fn expr(&mut self) -> Result<(), SyntaxError> {
+        let word = self.next("an expression")?;
+        let expr = match word.to_uppercase().as_str() {
+            "ADD" => Expr::ADD,
+            "SUB" => Expr::SUB,
+            "MUL" => Expr::MUL,
+            "DIV" => Expr::DIV,
+            "SIN" => Expr::SIN,
+            "COS" => Expr::COS,
+            "EXP" => Expr::EXP,
+            "LOG" => Expr::LOG,
+            "SQRT" => Expr::SQRT,
+            "POW" => Expr::POW,
+            "ABS" => Expr::ABS,
+            "NEG" => Expr::NEG,
+            "MIN" => Expr::MIN,
+            "MAX" => Expr::MAX,
+            "MOD" => Expr::MOD,
+            "FLOOR" => Expr::FLOOR,
+            "TANH" => Expr::TANH,
+            "EQ" => Expr::EQ,
+            "LT" => Expr::LT,
+            "GT" => Expr::GT,
+            "OR" => Expr::OR,
+            "AND" => Expr::AND,
+            "NOT" => Expr::NOT,
+            _ => {
+                if let Some(num) = register(word) {
+                    self.program.push(Token::Reg(num));
+                } else if let Ok(val) = word.parse() {
+                    self.program.push(Token::Expr(Expr::NUM(val)));
+                } else {
+                    return Err(self.error_at(
+                        self.pos - 1,
+                        format!("Expected an expression, got '{word}'"),
+                    ));
+                }
+                return Ok(());
+            }
+        };
+        self.program.push(Token::Expr(expr));
+        for _ in 0..expr.argnum() {
+            self.expr()?;
+        }
+        Ok(())
+    }
+
+    fn reg(&mut self) -> Result<(), SyntaxError> {
+        let word = self.next("a register")?;
+        match register(word) {
+            Some(num) => {
+                self.program.push(Token::Reg(num));
+                Ok(())
+            }
+            None => Err(self.error_at(self.pos - 1, format!("Expected a register, got '{word}'"))),
+        }
+    }
+
+    fn expect(&mut self, keyword: &str) -> Result<(), SyntaxError> {
+        let word = self.next(keyword)?;
+        if word.eq_ignore_ascii_case(keyword) {
+            Ok(())
+        } else {
+            Err(self.error_at(self.pos - 1, format!("Expected {keyword}, got '{word}'")))
+        }
+    }
+
+    fn next(&mut self, expected: &str) -> Result<&'a str, SyntaxError> {
+        match self.words.get(self.pos) {
+            Some(&(_, word)) => {
+                self.pos += 1;
+                Ok(word)
+            }
+            None => Err(self.error(
+                self.text.len()..self.text.len(),
+                format!("Expected {expected}, got the end of the program"),
+            )),
+        }
+    }
+
+    fn error_at(&self, word: usize, reason: String) -> SyntaxError {
+        self.error(self.words[word].0.clone(), reason)
+    }
+
+    fn error(&self, span: Range<usize>, reason: String) -> SyntaxError {
+        SyntaxError::new(self.text, span, reason)
+    }
+}
+
+// 1-based line and column of a byte offset
+fn location(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+fn register(word: &str) -> Option<usize> {
+    let digits = word.strip_prefix(['R', 'r'])?;
+    if digits.starts_with('+') {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::growing::random_programs;
+    use super::*;
+    use crate::params::Params;
+
+    #[test]
+    fn test_pprint() {
+        #[rustfmt::skip]
+        let program = vec![
+            Token::Stat(Stat::INPUT), Token::Reg(0),
+            Token::Stat(Stat::WHILE), Token::Expr(Expr::GT), Token::Reg(0), Token::Expr(Expr::NUM(0.0)),
+                Token::Stat(Stat::IF), Token::Expr(Expr::LT), Token::Reg(0), Token::Expr(Expr::NUM(3.5)),
+                    Token::Stat(Stat::OUTPUT), Token::Reg(0),
+                Token::ELSE,
+                    Token::Stat(Stat::LOAD), Token::Reg(1), Token::Expr(Expr::NUM(-1.25)),
+                Token::END,
+                Token::Stat(Stat::LOAD), Token::Reg(0), Token::Expr(Expr::SUB), Token::Reg(0), Token::Expr(Expr::NUM(1.0)),
+            Token::END,
+        ];
+        let text = "INPUT R0
+WHILE GT R0 0 DO
+  IF LT R0 3.5
+    OUTPUT R0
+  ELSE
+    LOAD R1 -1.25
+  END
+  LOAD R0 SUB R0 1
+END";
+        assert_eq!(pprint(&program), text);
+        assert_eq!(parse(text), Ok(program));
+    }
+
+    #[test]
+    fn test_parse_is_lenient_about_layout() {
+        let text = "# absolute value
+            input r0  if lt R0 0 load R0 mul R0 -1 end # negate
+            output R0";
+        assert_eq!(
+            pprint(&parse(text).unwrap()),
+            "INPUT R0\nIF LT R0 0\n  LOAD R0 MUL R0 -1\nEND\nOUTPUT R0"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |text| parse(text).unwrap_err();
+
+        let e = error("INPUT R0\nLOAD R0 ADD R0");
+        assert_eq!((e.line, e.column), (2, 15));
+        assert_eq!(
+            e.reason,
+            "Expected an expression, got the end of the program"
+        );
+
+        let e = error("INPUT R0\nOUTPUT  FOO");
+        assert_eq!((e.line, e.column, e.span.clone()), (2, 9, 17..20));
+        assert_eq!(
+            e.to_string(),
+            "2:9: Expected an expression, got 'FOO'\nOUTPUT  FOO\n        ^^^"
+        );
+
+        let e = error("IF R0\n  OUTPUT R0");
+        assert_eq!((e.line, e.column), (1, 1));
+        assert_eq!(e.reason, "IF at 1:1 is never closed with END");
+
+        assert_eq!(
+            error("WHILE R0 OUTPUT R0 END").reason,
+            "Expected DO, got 'OUTPUT'"
+        );
+        assert_eq!(error("OUTPUT R0 END").reason, "Unexpected END");
+        assert_eq!(error("INPUT 3").reason, "Expected a register, got '3'");
+        assert_eq!(
+            error("PRINT R0").reason,
+            "Expected a statement, got 'PRINT'"
+        );
+        assert_eq!(
+            error("IF R0 ELSE ELSE END").reason,
+            "Expected END, got 'ELSE'"
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let params = Params {
+            memsize: 4,
+            depth: 5,
+            ..Default::default()
+        };
+        for program in random_programs(5, 300, &params) {
+            assert_eq!(parse(&pprint(&program)), Ok(program));
+        }
+
+        let program = parse("OUTPUT POW EXP R0 MIN SQRT R1 NEG ABS R2").unwrap();
+        assert_eq!(
+            program[1..],
+            [
+                Token::Expr(Expr::POW),
+                Token::Expr(Expr::EXP),
+                Token::Reg(0),
+                Token::Expr(Expr::MIN),
+                Token::Expr(Expr::SQRT),
+                Token::Reg(1),
+                Token::Expr(Expr::NEG),
+                Token::Expr(Expr::ABS),
+                Token::Reg(2),
+            ]
+        );
+    }
+}
@@ -0,0 +1,233 @@
+// checks that a program can be run: statements and expressions get all of their arguments,
+// registers fit in the memory and every IF and WHILE is closed with END
+
+use std::{error::Error, fmt::Display};
+
+use super::common::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    // position of the offending token, the length of the program when one is missing
+    pub pos: usize,
+    pub reason: String,
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.pos, self.reason)
+    }
+}
+
+impl Error for ValidationError {}
+
+pub fn validate(program: &Program, memsize: usize) -> Result<(), Vec<ValidationError>> {
+    let mut validator = Validator {
+        program,
+        memsize,
+        errors: Vec::new(),
+    };
+    let mut pos = validator.block(0);
+    while pos < program.len() {
+        validator.error(
+            pos,
+            format!("{:?} without an IF or WHILE to close", program[pos]),
+        );
+        pos = validator.block(pos + 1);
+    }
+    if validator.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(validator.errors)
+    }
+}
+
+struct Validator<'a> {
+    program: &'a Program,
+    memsize: usize,
+    errors: Vec<ValidationError>,
+}
+
+// every method returns the position after what it checked, tokens of the wrong kind
+// are left for the enclosing block so that one mistake doesn't hide the rest
+impl Validator<'_> {
+    // statements up to the closing ELSE or END, returns its position
+    fn block(&mut self, pos: usize) -> usize {
+        let mut pos = pos;
+        while pos < self.program.len() && !matches!(self.program[pos], Token::ELSE | Token::END) {
+            pos = self.stat(pos);
+        }
+        pos
+    }
+
+    fn stat(&mut self, pos: usize) -> usize {
+        let Token::Stat(stat) = self.program[pos] else {
+            self.error(
+                pos,
+                format!("Expected a statement, got {:?}", self.program[pos]),
+            );
+            return pos + 1;
+        };
+        match stat {
+            Stat::INPUT => self.destination(pos + 1),
+            Stat::LOAD => {
+                let expr_pos = self.destination(pos + 1);
+                self.expr(expr_pos)
+            }
+            Stat::OUTPUT => self.expr(pos + 1),
+            Stat::IF | Stat::WHILE => {
+                let block_pos = self.expr(pos + 1);
+                let mut closing = self.block(block_pos);
+                if stat == Stat::IF && self.program.get(closing) == Some(&Token::ELSE) {
+                    closing = self.block(closing + 1);
+                }
+                while self.program.get(closing) == Some(&Token::ELSE) {
+                    self.error(
+                        closing,
+                        format!("Unexpected ELSE in the {stat:?} started at {pos}"),
+                    );
+                    closing = self.block(closing + 1);
+                }
+                if closing < self.program.len() {
+                    closing + 1
+                } else {
+                    self.error(
+                        closing,
+                        format!("{stat:?} started at {pos} is never closed with END"),
+                    );
+                    closing
+                }
+            }
+        }
+    }
+
+    fn expr(&mut self, pos: usize) -> usize {
+        match self.program.get(pos) {
+            Some(Token::Expr(expr)) => {
+                let mut end = pos + 1;
+                for _ in 0..expr.argnum() {
+                    end = self.expr(end);
+                }
+                end
+            }
+            Some(&Token::Reg(num)) => {
+                self.register(pos, num);
+                pos + 1
+            }
+            Some(token) => {
+                self.error(pos, format!("Expected an expression, got {token:?}"));
+                pos
+            }
+            None => {
+                self.error(
+                    pos,
+                    "Expected an expression, got the end of the program".into(),
+                );
+                pos
+            }
+        }
+    }
+
+    fn destination(&mut self, pos: usize) -> usize {
+        match self.program.get(pos) {
+            Some(&Token::Reg(num)) => {
+                self.register(pos, num);
+                pos + 1
+            }
+            Some(token) => {
+                self.error(pos, format!("Expected a register, got {token:?}"));
+                pos
+            }
+            None => {
+                self.error(
+                    pos,
+                    "Expected a register, got the end of the program".into(),
+                );
+                pos
+            }
+        }
+    }
+
+    fn register(&mut self, pos: usize, num: usize) {
+        if num >= self.memsize {
+            self.error(
+                pos,
+                format!(
+                    "R{num} is outside of the memory of {} registers",
+                    self.memsize
+                ),
+            );
+        }
+    }
+
+    // a missing token is reported once, even if several nodes lack it
+    fn error(&mut self, pos: usize, reason: String) {
+        let error = ValidationError { pos, reason };
+        if self.errors.last() != Some(&error) {
+            self.errors.push(error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::growing::random_programs;
+    use super::*;
+    use crate::params::Params;
+
+    #[test]
+    fn test_grown_programs_are_valid() {
+        let params = Params {
+            memsize: 3,
+            depth: 5,
+            ..Default::default()
+        };
+        for program in random_programs(2, 500, &params) {
+            assert_eq!(validate(&program, params.memsize), Ok(()), "{program:?}");
+        }
+    }
+
+    #[test]
+    fn test_invalid_programs() {
+        let errors = |program: &Program| {
+            validate(program, 2)
+                .unwrap_err()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        #[rustfmt::skip]
+        let program = vec![
+            Token::Stat(Stat::LOAD), Token::Reg(2), Token::Expr(Expr::ADD), Token::Reg(0),
+            Token::Stat(Stat::OUTPUT), Token::Reg(1),
+        ];
+        assert_eq!(
+            errors(&program),
+            [
+                "1: R2 is outside of the memory of 2 registers",
+                "4: Expected an expression, got Stat(OUTPUT)",
+            ]
+        );
+
+        #[rustfmt::skip]
+        let program = vec![
+            Token::Stat(Stat::WHILE), Token::Reg(0),
+                Token::Stat(Stat::INPUT), Token::Expr(Expr::NUM(1.0)),
+            Token::ELSE,
+            Token::END,
+            Token::END,
+            Token::Stat(Stat::IF), Token::Expr(Expr::NOT),
+        ];
+        assert_eq!(
+            errors(&program),
+            [
+                "3: Expected a register, got Expr(NUM(1.0))",
+                "3: Expected a statement, got Expr(NUM(1.0))",
+                "4: Unexpected ELSE in the WHILE started at 0",
+                "6: END without an IF or WHILE to close",
+                "9: Expected an expression, got the end of the program",
+                "9: IF started at 7 is never closed with END",
+            ]
+        );
+    }
+}